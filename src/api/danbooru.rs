@@ -0,0 +1,634 @@
+//! The [Danbooru](https://danbooru.donmai.us/) backend.
+//!
+//! Usually, you prefer to use [`Danbooru`] with [`crate::api::BatchGetter`],
+//! [`Getter`] is the low-level way to get the raw [`data`] from the Danbooru API.
+
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::LazyLock;
+
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+
+use super::data::field::Rating;
+use super::{Booru, Page, RequestOptions};
+
+/// The URLs for the Danbooru API.
+pub mod url {
+    use super::*;
+
+    /// The base URL of the Danbooru.
+    pub const BASE_URL: &str = "https://danbooru.donmai.us/";
+
+    /// The path of the posts API, relative to the base URL.
+    ///
+    /// See: <https://danbooru.donmai.us/wiki_pages/api:posts>
+    pub const POSTS_PATH: &str = "posts.json";
+
+    /// The path of the counts API, relative to the base URL.
+    ///
+    /// See: <https://danbooru.donmai.us/wiki_pages/api:counts>
+    pub const COUNTS_PATH: &str = "counts/posts.json";
+
+    /// The parsed [`BASE_URL`].
+    pub static BASE: LazyLock<Url> = LazyLock::new(|| Url::parse(BASE_URL).unwrap());
+}
+
+/// The data structure for the JSON response from the Danbooru API.
+pub mod data {
+    use super::*;
+
+    /// The fields of the JSON response.
+    pub mod field {
+        use super::*;
+
+        /// The post of the posts API.
+        ///
+        /// This is the raw post of Danbooru,
+        /// it can be converted into the common [`crate::api::data::field::Post`]
+        /// only if `md5` and `file_url` are present.
+        #[non_exhaustive]
+        #[derive(Debug, Deserialize, Serialize)]
+        pub struct Post {
+            /// The ID of the image.
+            pub id: u64,
+            /// The MD5 hash of the image.
+            /// `None` if the post is restricted to the current user.
+            pub md5: Option<String>,
+            /// The URL of the image, which can be used to download the image.
+            /// `None` if the post is restricted to the current user.
+            pub file_url: Option<String>,
+            /// The extension of the image, e.g. `jpg`.
+            pub file_ext: String,
+            /// The artist tags, separated by spaces.
+            pub tag_string_artist: String,
+            /// The character tags, separated by spaces.
+            pub tag_string_character: String,
+            /// The copyright tags, separated by spaces.
+            pub tag_string_copyright: String,
+            /// The general tags, separated by spaces.
+            pub tag_string_general: String,
+            /// The meta tags, separated by spaces.
+            pub tag_string_meta: String,
+            /// The rating, one of `g`, `s`, `q` or `e`.
+            pub rating: Option<String>,
+            /// The score of the image.
+            pub score: Option<i64>,
+            /// The width of the image in pixels.
+            pub image_width: Option<u64>,
+            /// The height of the image in pixels.
+            pub image_height: Option<u64>,
+            /// The source of the image, may be empty.
+            pub source: Option<String>,
+            /// The creation time in ISO 8601, e.g. `2024-08-10T13:51:42.123-04:00`.
+            pub created_at: Option<String>,
+            /// The ID of the parent post.
+            pub parent_id: Option<u64>,
+            /// Whether the post has child posts.
+            pub has_children: Option<bool>,
+            /// The URL of the resized sample image.
+            /// `None` if the post is restricted to the current user.
+            pub large_file_url: Option<String>,
+            /// The URL of the thumbnail.
+            /// `None` if the post is restricted to the current user.
+            pub preview_file_url: Option<String>,
+            /// The file size of the image in bytes.
+            pub file_size: Option<u64>,
+        }
+
+        /// The `counts` field of the counts API.
+        #[non_exhaustive]
+        #[derive(Debug, Deserialize, Serialize)]
+        pub struct Counts {
+            /// The total number of posts matching the tags.
+            /// `None` if counting timed out, e.g. for complex searches.
+            pub posts: Option<u64>,
+        }
+    }
+
+    /// The JSON structure response from the counts API.
+    #[non_exhaustive]
+    #[derive(Debug, Deserialize, Serialize)]
+    pub struct CountsJson {
+        /// The counts of the response.
+        pub counts: field::Counts,
+    }
+}
+
+impl TryFrom<data::field::Post> for super::data::field::Post {
+    /// The raw post is returned if it is restricted.
+    type Error = data::field::Post;
+
+    /// The `tags` is joined in the order of artist, character, copyright, general and meta tags.
+    /// The `image` is `{md5}.{file_ext}`, as Danbooru doesn't return the original file name.
+    /// The empty `source` is mapped to `None`.
+    fn try_from(value: data::field::Post) -> Result<Self, Self::Error> {
+        let (md5, file_url) = match (&value.md5, &value.file_url) {
+            (Some(md5), Some(file_url)) => (md5.clone(), file_url.clone()),
+            _ => return Err(value),
+        };
+
+        let tags = [
+            &value.tag_string_artist,
+            &value.tag_string_character,
+            &value.tag_string_copyright,
+            &value.tag_string_general,
+            &value.tag_string_meta,
+        ]
+        .into_iter()
+        .filter(|tags| !tags.is_empty())
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(" ");
+        let image = PathBuf::from(format!("{md5}.{}", value.file_ext));
+
+        let rating = value.rating.as_deref().and_then(|rating| match rating {
+            "g" => Some(Rating::General),
+            "s" => Some(Rating::Sensitive),
+            "q" => Some(Rating::Questionable),
+            "e" => Some(Rating::Explicit),
+            _ => None,
+        });
+
+        Ok(Self {
+            rating,
+            score: value.score,
+            width: value.image_width,
+            height: value.image_height,
+            source: value.source.filter(|source| !source.is_empty()),
+            created_at: value.created_at,
+            parent_id: value.parent_id,
+            has_children: value.has_children,
+            sample_url: value.large_file_url,
+            preview_url: value.preview_file_url,
+            file_size: value.file_size,
+            ..Self::new(value.id, md5, file_url, tags, image)
+        })
+    }
+}
+
+/// A Consuming-Builders style function to get the posts from the Danbooru API.
+///
+/// # Example
+///
+/// ```rust
+/// use reqwest::Client;
+/// use booru_dl::api::danbooru::Getter;
+///
+/// #[tokio::main]
+/// async fn main() -> reqwest::Result<()> {
+///     let client = Client::new();
+///     let tags = "cat";
+///     let limit = 10;
+///     let page = 1;
+///
+///     let posts = Getter::build(&client, &tags, limit, page)
+///         .expect("illegal arguments")
+///         .run()
+///         .await?;
+///
+///     Ok(())
+/// }
+/// ```
+pub struct Getter<'a> {
+    client: &'a Client,
+    base_url: &'a Url,
+    options: &'a RequestOptions,
+    tags: &'a str,
+    limit: u64,
+    page: u64,
+}
+
+impl Getter<'_> {
+    /// See <https://danbooru.donmai.us/wiki_pages/api:posts> for arguments.
+    ///
+    /// Note: `page` is one-based.
+    ///
+    /// # Errors
+    ///
+    /// If `tags` is empty, or `limit` is not in the range `1..=200`,
+    /// or `page` is 0, this function will return an error.
+    pub fn build<'a>(
+        client: &'a Client,
+        tags: &'a str,
+        limit: u64,
+        page: u64,
+    ) -> anyhow::Result<Getter<'a>> {
+        if tags.is_empty() {
+            return Err(anyhow::anyhow!("Tags cannot be empty"));
+        }
+        // This is danbooru's limit.
+        // see: https://danbooru.donmai.us/wiki_pages/help:users
+        if !matches!(limit, 1..=Danbooru::MAX_LIMIT) {
+            return Err(anyhow::anyhow!("Limit can only be between 1 and 200"));
+        }
+        if page == 0 {
+            return Err(anyhow::anyhow!("Page starts from 1"));
+        }
+        Ok(Getter {
+            client,
+            base_url: &url::BASE,
+            options: &RequestOptions::DEFAULT,
+            tags,
+            limit,
+            page,
+        })
+    }
+
+    /// Send the request to the Danbooru API and get the posts.
+    ///
+    /// Note: Danbooru returns at most 1000 pages for anonymous users.
+    ///
+    /// # Errors
+    ///
+    /// If the request still fails after retrying with [`Self::options`],
+    /// or the server returns an error status, this function will return an error.
+    pub async fn run(self) -> reqwest::Result<Vec<data::field::Post>> {
+        let mut target_url = self.base_url.join(url::POSTS_PATH).unwrap();
+        target_url.query_pairs_mut().extend_pairs([
+            ("tags", self.tags),
+            ("limit", &self.limit.to_string()),
+            ("page", &self.page.to_string()),
+        ]);
+        self.options.send_json(self.client.get(target_url)).await
+    }
+}
+
+impl<'a> Getter<'a> {
+    /// Use another Danbooru instance instead of [`url::BASE_URL`].
+    ///
+    /// The `base_url` should end with `/`, or its last path segment will be replaced.
+    pub fn base_url(mut self, base_url: &'a Url) -> Self {
+        self.base_url = base_url;
+        self
+    }
+
+    /// Send the requests with `options`, default to [`RequestOptions::DEFAULT`].
+    pub fn options(mut self, options: &'a RequestOptions) -> Self {
+        self.options = options;
+        self
+    }
+}
+
+/// The [`Booru`] implementation for Danbooru, which wraps [`Getter`].
+///
+/// `page` of [`Booru::search`] is zero-based, but Danbooru's `page` is one-based.
+/// Restricted posts without `md5` or `file_url` are skipped.
+#[non_exhaustive]
+#[derive(Debug, Clone)]
+pub struct Danbooru {
+    /// The base URL of the Danbooru instance, see [`Getter::base_url`].
+    pub base_url: Url,
+    /// The options of the API requests, see [`Getter::options`].
+    pub options: RequestOptions,
+}
+
+impl Danbooru {
+    /// The maximum number of posts in a single page.
+    pub const MAX_LIMIT: u64 = 200;
+
+    /// The maximum one-based `page` that Danbooru accepts for anonymous users.
+    pub const MAX_PAGE: u64 = 1000;
+
+    /// Create a backend for the Danbooru instance at `base_url`.
+    pub fn new(base_url: Url) -> Self {
+        Self {
+            base_url,
+            options: RequestOptions::DEFAULT,
+        }
+    }
+
+    /// Send the API requests with `options`, see [`Getter::options`].
+    pub fn with_options(mut self, options: RequestOptions) -> Self {
+        self.options = options;
+        self
+    }
+}
+
+impl Default for Danbooru {
+    fn default() -> Self {
+        Self::new(url::BASE.clone())
+    }
+}
+
+impl Booru for Danbooru {
+    #[inline]
+    fn max_limit(&self) -> u64 {
+        Self::MAX_LIMIT
+    }
+
+    /// Danbooru returns an error if `page > 1000` for anonymous users.
+    #[inline]
+    fn max_page(&self, _limit: u64) -> Option<u64> {
+        // zero-based
+        Some(Self::MAX_PAGE - 1)
+    }
+
+    fn search<'a>(
+        &'a self,
+        client: &'a Client,
+        tags: &'a str,
+        limit: u64,
+        page: u64,
+    ) -> impl Future<Output = reqwest::Result<Page>> + Send + 'a {
+        let getter = Getter::build(client, tags, limit, page + 1)
+            .expect("illegal search arguments")
+            .base_url(&self.base_url)
+            .options(&self.options);
+        async move {
+            let raw_posts = getter.run().await?;
+            let exhausted = raw_posts.is_empty();
+            let posts = raw_posts
+                .into_iter()
+                .filter_map(|post| post.try_into().ok())
+                .collect();
+            Ok(Page::new(posts, None).with_exhausted(exhausted))
+        }
+    }
+
+    /// The count is optional for pagination, so `None` is returned if the counts API fails,
+    /// then [`BatchGetter`](crate::api::BatchGetter) pages until the result is empty.
+    async fn count<'a>(
+        &'a self,
+        client: &'a Client,
+        tags: &'a str,
+    ) -> reqwest::Result<Option<u64>> {
+        let mut target_url = self.base_url.join(url::COUNTS_PATH).unwrap();
+        target_url.query_pairs_mut().append_pair("tags", tags);
+        let data: reqwest::Result<data::CountsJson> =
+            self.options.send_json(client.get(target_url)).await;
+        Ok(data.ok().and_then(|data| data.counts.posts))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::num::NonZeroU32;
+    use std::time::Duration;
+
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use crate::api::BatchGetter;
+    use crate::retry::RetryPolicy;
+
+    /// A `posts.json?tags=cat&limit=2` response in the full format of Danbooru,
+    /// including the fields this crate ignores, but with made-up values.
+    /// The second post is restricted, so Danbooru omits its `md5` and file URLs.
+    ///
+    /// Refresh it from `https://danbooru.donmai.us/posts.json?tags=cat&limit=2` if the format changes.
+    const POSTS_JSON: &str = r#"[
+        {
+            "id": 7948127,
+            "created_at": "2024-08-10T13:51:42.123-04:00",
+            "uploader_id": 508240,
+            "score": 15,
+            "source": "",
+            "md5": "b4c3bd5fd4c25eb4c8dd20a4e1d4a4f5",
+            "last_comment_bumped_at": null,
+            "rating": "s",
+            "image_width": 2894,
+            "image_height": 4093,
+            "tag_string": "1girl animal_ears cat highres kuroi_mimei original",
+            "fav_count": 21,
+            "file_ext": "jpg",
+            "last_noted_at": null,
+            "parent_id": null,
+            "has_children": false,
+            "approver_id": null,
+            "tag_count_general": 3,
+            "tag_count_artist": 1,
+            "tag_count_character": 0,
+            "tag_count_copyright": 1,
+            "file_size": 1048576,
+            "up_score": 15,
+            "down_score": 0,
+            "is_pending": false,
+            "is_flagged": false,
+            "is_deleted": false,
+            "tag_count": 6,
+            "updated_at": "2024-08-11T02:03:04.567-04:00",
+            "is_banned": false,
+            "pixiv_id": null,
+            "last_commented_at": null,
+            "has_active_children": false,
+            "bit_flags": 0,
+            "tag_count_meta": 1,
+            "has_large": true,
+            "has_visible_children": false,
+            "media_asset": {
+                "id": 20193512,
+                "created_at": "2024-08-10T13:51:30.456-04:00",
+                "updated_at": "2024-08-10T13:51:35.789-04:00",
+                "md5": "b4c3bd5fd4c25eb4c8dd20a4e1d4a4f5",
+                "file_ext": "jpg",
+                "file_size": 1048576,
+                "image_width": 2894,
+                "image_height": 4093,
+                "duration": null,
+                "status": "active",
+                "file_key": "Xq3bZ9wLp",
+                "is_public": true,
+                "pixel_hash": "0d5c2a3b1f4e6d7c8b9a0f1e2d3c4b5a",
+                "variants": [
+                    {
+                        "type": "180x180",
+                        "url": "https://cdn.donmai.us/180x180/b4/c3/b4c3bd5fd4c25eb4c8dd20a4e1d4a4f5.jpg",
+                        "width": 127,
+                        "height": 180,
+                        "file_ext": "jpg"
+                    },
+                    {
+                        "type": "original",
+                        "url": "https://cdn.donmai.us/original/b4/c3/b4c3bd5fd4c25eb4c8dd20a4e1d4a4f5.jpg",
+                        "width": 2894,
+                        "height": 4093,
+                        "file_ext": "jpg"
+                    }
+                ]
+            },
+            "tag_string_general": "1girl animal_ears cat",
+            "tag_string_character": "",
+            "tag_string_copyright": "original",
+            "tag_string_artist": "kuroi_mimei",
+            "tag_string_meta": "highres",
+            "file_url": "https://cdn.donmai.us/original/b4/c3/b4c3bd5fd4c25eb4c8dd20a4e1d4a4f5.jpg",
+            "large_file_url": "https://cdn.donmai.us/sample/b4/c3/sample-b4c3bd5fd4c25eb4c8dd20a4e1d4a4f5.jpg",
+            "preview_file_url": "https://cdn.donmai.us/180x180/b4/c3/b4c3bd5fd4c25eb4c8dd20a4e1d4a4f5.jpg"
+        },
+        {
+            "id": 7948120,
+            "created_at": "2024-08-10T13:40:11.222-04:00",
+            "uploader_id": 499293,
+            "score": 4,
+            "source": "https://twitter.com/example/status/1822345678901234567",
+            "last_comment_bumped_at": null,
+            "rating": "g",
+            "image_width": 1200,
+            "image_height": 900,
+            "tag_string": "cat no_humans",
+            "fav_count": 3,
+            "file_ext": "png",
+            "last_noted_at": null,
+            "parent_id": null,
+            "has_children": false,
+            "approver_id": null,
+            "tag_count_general": 2,
+            "tag_count_artist": 0,
+            "tag_count_character": 0,
+            "tag_count_copyright": 0,
+            "file_size": 524288,
+            "up_score": 4,
+            "down_score": 0,
+            "is_pending": false,
+            "is_flagged": false,
+            "is_deleted": false,
+            "tag_count": 2,
+            "updated_at": "2024-08-10T13:40:11.222-04:00",
+            "is_banned": true,
+            "pixiv_id": null,
+            "last_commented_at": null,
+            "has_active_children": false,
+            "bit_flags": 0,
+            "tag_count_meta": 0,
+            "has_large": false,
+            "has_visible_children": false,
+            "media_asset": {
+                "id": 20193480,
+                "created_at": "2024-08-10T13:40:01.333-04:00",
+                "updated_at": "2024-08-10T13:40:05.444-04:00",
+                "file_ext": "png",
+                "file_size": 524288,
+                "image_width": 1200,
+                "image_height": 900,
+                "duration": null,
+                "status": "active",
+                "is_public": false
+            },
+            "tag_string_general": "cat no_humans",
+            "tag_string_character": "",
+            "tag_string_copyright": "",
+            "tag_string_artist": "",
+            "tag_string_meta": ""
+        }
+    ]"#;
+    /// A `counts/posts.json?tags=cat` response.
+    const COUNTS_JSON: &str = r#"{"counts":{"posts":3}}"#;
+
+    async fn mock_server(counts: ResponseTemplate) -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/posts.json"))
+            .and(query_param("page", "1"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(POSTS_JSON, "application/json"))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/posts.json"))
+            .and(query_param("page", "2"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw("[]", "application/json"))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/counts/posts.json"))
+            .respond_with(counts)
+            .mount(&server)
+            .await;
+        server
+    }
+
+    #[test]
+    fn test_illegal_args() {
+        let client = Client::new();
+
+        assert!(Getter::build(&client, "", 100, 1).is_err());
+        assert!(Getter::build(&client, "cat", 201, 1).is_err());
+        assert!(Getter::build(&client, "cat", 100, 0).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_search_fake_server() -> reqwest::Result<()> {
+        let server =
+            mock_server(ResponseTemplate::new(200).set_body_raw(COUNTS_JSON, "application/json"))
+                .await;
+        let client = Client::new();
+        let booru = Danbooru::new(server.uri().parse().unwrap());
+
+        let page = booru.search(&client, "cat", 2, 0).await?;
+        // the restricted post is skipped
+        assert_eq!(page.posts.len(), 1);
+        let post = &page.posts[0];
+        assert_eq!(post.id, 7948127);
+        assert_eq!(
+            post.tags,
+            "kuroi_mimei original 1girl animal_ears cat highres"
+        );
+        assert_eq!(post.filename, PathBuf::from("7948127.jpg"));
+        assert_eq!(post.rating, Some(Rating::Sensitive));
+        assert_eq!(post.score, Some(15));
+        assert_eq!((post.width, post.height), (Some(2894), Some(4093)));
+        assert_eq!(post.source, None);
+        assert_eq!(post.parent_id, None);
+        assert_eq!(post.has_children, Some(false));
+        assert_eq!(post.file_size, Some(1048576));
+
+        assert_eq!(booru.count(&client, "cat").await?, Some(3));
+
+        // the count is 3, but there is only one downloadable post
+        let posts = BatchGetter::build(&client, &booru, "cat", 10)
+            .unwrap()
+            .run()
+            .await?;
+        assert_eq!(posts.len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_getter_restricted_post() -> reqwest::Result<()> {
+        let server = mock_server(ResponseTemplate::new(200)).await;
+        let client = Client::new();
+        let base_url = server.uri().parse().unwrap();
+
+        let posts = Getter::build(&client, "cat", 2, 1)
+            .unwrap()
+            .base_url(&base_url)
+            .run()
+            .await?;
+        assert_eq!(posts.len(), 2);
+        let restricted = &posts[1];
+        assert_eq!(restricted.id, 7948120);
+        assert_eq!(restricted.md5, None);
+        assert_eq!(restricted.file_url, None);
+        assert_eq!(restricted.preview_file_url, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_count_fallback() -> reqwest::Result<()> {
+        let client = Client::new();
+
+        // the counts API fails, so we page until the result is empty
+        let server = mock_server(ResponseTemplate::new(500)).await;
+        let booru = Danbooru::new(server.uri().parse().unwrap()).with_options(
+            RequestOptions::DEFAULT.retry(RetryPolicy::new(NonZeroU32::MIN, Duration::ZERO)),
+        );
+        assert_eq!(booru.count(&client, "cat").await?, None);
+        let posts = BatchGetter::build(&client, &booru, "cat", 10)
+            .unwrap()
+            .run()
+            .await?;
+        assert_eq!(posts.len(), 1);
+
+        // the counting timed out
+        let server = mock_server(
+            ResponseTemplate::new(200)
+                .set_body_raw(r#"{"counts":{"posts":null}}"#, "application/json"),
+        )
+        .await;
+        let booru = Danbooru::new(server.uri().parse().unwrap());
+        assert_eq!(booru.count(&client, "cat").await?, None);
+        Ok(())
+    }
+}
@@ -0,0 +1,770 @@
+//! The [Gelbooru](https://gelbooru.com/) backend.
+//!
+//! Usually, you prefer to use [`Gelbooru`] with [`crate::api::BatchGetter`],
+//! [`Getter`] is the low-level way to get the raw [`data`] from the Gelbooru API.
+//!
+//! Sites running Gelbooru 0.2, e.g. [Safebooru](https://safebooru.org/) or [Rule34](https://rule34.xxx/),
+//! share the same `dapi` protocol, see [`Gelbooru::new`].
+
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::LazyLock;
+
+use reqwest::{Client, Url};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use super::data::field::Rating;
+use super::{Booru, Page, RequestOptions};
+use crate::tool::Secret;
+
+/// The URLs for the Gelbooru API.
+pub mod url {
+    use super::*;
+
+    /// The base URL of the Gelbooru.
+    pub const BASE_URL: &str = "https://gelbooru.com/index.php";
+
+    /// The parsed [`BASE_URL`].
+    pub static BASE: LazyLock<Url> = LazyLock::new(|| Url::parse(BASE_URL).unwrap());
+
+    /// The Api URL of the Gelbooru, which can be used to query gelbooru's database.
+    pub static API_URL: LazyLock<Url> = LazyLock::new(|| api_url(&BASE));
+
+    /// The Post URL of the Gelbooru, which can be used to display the images.
+    pub static POST_URL: LazyLock<Url> = LazyLock::new(|| post_url(&BASE));
+
+    /// Build the Api URL from the `base_url` of a Gelbooru compatible site.
+    pub fn api_url(base_url: &Url) -> Url {
+        // see: https://gelbooru.com/index.php?page=wiki&s=view&id=18780
+        let mut url = base_url.clone();
+        url.query_pairs_mut().extend_pairs([
+            ("page", "dapi"),
+            ("s", "post"),
+            ("q", "index"),
+            ("json", "1"),
+        ]);
+        url
+    }
+
+    /// Build the Post URL from the `base_url` of a Gelbooru compatible site.
+    pub fn post_url(base_url: &Url) -> Url {
+        // see: https://gelbooru.com/index.php?page=wiki&s=view&id=18780
+        let mut url = base_url.clone();
+        url.query_pairs_mut()
+            .extend_pairs([("page", "post"), ("s", "list"), ("q", "index")]);
+        url
+    }
+}
+
+/// The data structure for the JSON response from the Gelbooru API.
+pub mod data {
+    use super::*;
+
+    /// The fields of the [`Json`] response.
+    pub mod field {
+        use super::*;
+
+        /// The attributes field of the JSON response.
+        #[non_exhaustive]
+        #[derive(Debug, Deserialize, Serialize)]
+        pub struct Attributes {
+            /// The number of images in this response. Range: `0..=100`.
+            pub limit: u64,
+            /// The current index of the first image in this response.
+            pub offset: u64,
+            /// The total number of images in the gelbooru.
+            pub count: u64,
+        }
+
+        /// Gelbooru returns `"true"` or `"false"`, while Gelbooru 0.2 returns a bool.
+        fn deserialize_bool_or_str<'de, D>(deserializer: D) -> Result<Option<bool>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            #[derive(Deserialize)]
+            #[serde(untagged)]
+            enum BoolOrStr {
+                Bool(bool),
+                Str(String),
+            }
+
+            Ok(match Option::<BoolOrStr>::deserialize(deserializer)? {
+                Some(BoolOrStr::Bool(value)) => Some(value),
+                Some(BoolOrStr::Str(value)) => value.parse().ok(),
+                None => None,
+            })
+        }
+
+        /// The post field of the JSON response.
+        ///
+        /// This is the raw post of Gelbooru,
+        /// it can be converted into the common [`crate::api::data::field::Post`].
+        ///
+        /// The optional fields may be absent on Gelbooru 0.2 sites.
+        #[non_exhaustive]
+        #[derive(Debug, Deserialize, Serialize)]
+        pub struct Post {
+            /// The ID of the image.
+            pub id: u64,
+            /// The MD5 hash of the image.
+            ///
+            /// Gelbooru 0.2 names this field `hash`.
+            #[serde(alias = "hash")]
+            pub md5: String,
+            /// The URL of the image, which can be used to download the image.
+            pub file_url: String,
+            /// The tags of the image. Note: these tags are marked by gelbooru.
+            pub tags: String,
+            /// The original file name of the image.
+            pub image: PathBuf,
+            /// The rating, e.g. `general`, `sensitive`, `questionable` or `explicit`.
+            ///
+            /// Gelbooru 0.2 uses `safe` instead of `general` and `sensitive`.
+            pub rating: Option<String>,
+            /// The score of the image.
+            pub score: Option<i64>,
+            /// The width of the image in pixels.
+            pub width: Option<u64>,
+            /// The height of the image in pixels.
+            pub height: Option<u64>,
+            /// The source of the image, may be empty.
+            pub source: Option<String>,
+            /// The creation time, e.g. `Sat Aug 10 13:51:42 -0500 2024`.
+            pub created_at: Option<String>,
+            /// The name of the uploader.
+            pub owner: Option<String>,
+            /// The ID of the parent post, `0` if there is no parent.
+            pub parent_id: Option<u64>,
+            /// Whether the post has child posts.
+            #[serde(default, deserialize_with = "deserialize_bool_or_str")]
+            pub has_children: Option<bool>,
+            /// The URL of the resized sample image, may be empty.
+            pub sample_url: Option<String>,
+            /// The URL of the thumbnail.
+            pub preview_url: Option<String>,
+            /// The moderation status, e.g. `active`.
+            pub status: Option<String>,
+        }
+    }
+
+    /// The JSON structure response from the Gelbooru API.
+    #[non_exhaustive]
+    #[derive(Debug, Deserialize, Serialize)]
+    #[serde(from = "JsonRepr")]
+    pub struct Json {
+        #[serde(rename = "@attributes")]
+        /// The attributes of the JSON response.
+        /// Gelbooru 0.2 returns a bare array of posts without attributes,
+        /// in this case, this field will be `None`.
+        pub attributes: Option<field::Attributes>,
+        /// The posts of the JSON response.
+        /// if `attributes.count` is `0`, or `attributes.pid` is out of range,
+        /// this field will be `None`.
+        pub post: Option<Vec<field::Post>>,
+    }
+
+    /// All the JSON shapes returned by Gelbooru compatible sites.
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum JsonRepr {
+        Gelbooru {
+            #[serde(rename = "@attributes")]
+            attributes: field::Attributes,
+            post: Option<Vec<field::Post>>,
+        },
+        Legacy(Vec<field::Post>),
+    }
+
+    impl From<JsonRepr> for Json {
+        fn from(value: JsonRepr) -> Self {
+            match value {
+                JsonRepr::Gelbooru { attributes, post } => Self {
+                    attributes: Some(attributes),
+                    post,
+                },
+                JsonRepr::Legacy(post) => Self {
+                    attributes: None,
+                    post: (!post.is_empty()).then_some(post),
+                },
+            }
+        }
+    }
+}
+
+impl From<data::field::Post> for super::data::field::Post {
+    /// The empty `source` and `sample_url`, and the zero `parent_id` are mapped to `None`.
+    /// Gelbooru doesn't return the file size, so `file_size` is always `None`.
+    fn from(value: data::field::Post) -> Self {
+        let rating = value.rating.as_deref().and_then(|rating| match rating {
+            "general" | "safe" => Some(Rating::General),
+            "sensitive" => Some(Rating::Sensitive),
+            "questionable" => Some(Rating::Questionable),
+            "explicit" => Some(Rating::Explicit),
+            _ => None,
+        });
+        Self {
+            rating,
+            score: value.score,
+            width: value.width,
+            height: value.height,
+            source: value.source.filter(|source| !source.is_empty()),
+            created_at: value.created_at,
+            owner: value.owner,
+            parent_id: value.parent_id.filter(|&parent_id| parent_id != 0),
+            has_children: value.has_children,
+            sample_url: value.sample_url.filter(|sample_url| !sample_url.is_empty()),
+            preview_url: value.preview_url,
+            status: value.status,
+            ..Self::new(value.id, value.md5, value.file_url, value.tags, value.image)
+        }
+    }
+}
+
+/// The credentials for authenticated requests.
+///
+/// See: <https://gelbooru.com/index.php?page=account&s=options> to get your `api_key` and `user_id`.
+#[non_exhaustive]
+#[derive(Debug, Clone)]
+pub struct Credentials {
+    /// The user ID.
+    pub user_id: String,
+    /// The API key, which is never displayed.
+    pub api_key: Secret,
+}
+
+impl Credentials {
+    /// Create new credentials.
+    pub fn new(user_id: impl Into<String>, api_key: Secret) -> Self {
+        Self {
+            user_id: user_id.into(),
+            api_key,
+        }
+    }
+}
+
+/// A Consuming-Builders style function to get the data from the Gelbooru API.
+///
+/// # Example
+///
+/// ```rust
+/// use reqwest::Client;
+/// use booru_dl::api::gelbooru::Getter;
+///
+/// #[tokio::main]
+/// async fn main() -> reqwest::Result<()> {
+///     let client = Client::new();
+///     let tags = "cat";
+///     let limit = 10;
+///     let pid = 0;
+///
+///     let data = Getter::build(&client, &tags, limit, pid)
+///         .expect("illegal arguments")
+///         .run()
+///         .await?;
+///
+///     Ok(())
+/// }
+/// ```
+pub struct Getter<'a> {
+    client: &'a Client,
+    base_url: &'a Url,
+    options: &'a RequestOptions,
+    credentials: Option<&'a Credentials>,
+    tags: &'a str,
+    limit: u64,
+    pid: u64,
+}
+
+impl Getter<'_> {
+    /// See <https://gelbooru.com/index.php?page=wiki&s=view&id=18780> for arguments.
+    ///
+    /// # Errors
+    ///
+    /// If `tags` is empty, or `limit` is not in the range `1..=100`, this function will return an error.
+    pub fn build<'a>(
+        client: &'a Client,
+        tags: &'a str,
+        limit: u64,
+        pid: u64,
+    ) -> anyhow::Result<Getter<'a>> {
+        if tags.is_empty() {
+            return Err(anyhow::anyhow!("Tags cannot be empty"));
+        }
+        // This is gelbooru's limit.
+        // see: https://gelbooru.com/index.php?page=wiki&s=view&id=18780
+        if !matches!(limit, 1..=Gelbooru::MAX_LIMIT) {
+            return Err(anyhow::anyhow!("Limit can only be between 1 and 100"));
+        }
+        Ok(Getter {
+            client,
+            base_url: &url::BASE,
+            options: &RequestOptions::DEFAULT,
+            credentials: None,
+            tags,
+            limit,
+            pid,
+        })
+    }
+
+    /// Send the request to the Gelbooru API and get the JSON response.
+    ///
+    /// # Errors
+    ///
+    /// If the request still fails after retrying with [`Self::options`],
+    /// or the server returns an error status, this function will return an error.
+    ///
+    /// If [`Self::credentials`] is set, the URL will be stripped from the error,
+    /// so that the credentials never leak into error messages.
+    ///
+    /// <div class="warning">
+    ///
+    /// If `limit * pid > 20_000`, the API will return an error.
+    ///
+    /// See: <https://gelbooru.com/index.php?page=forum&s=view&id=1549>
+    ///
+    /// </div>
+    pub async fn run(self) -> reqwest::Result<data::Json> {
+        let mut target_url = url::api_url(self.base_url);
+        target_url.query_pairs_mut().extend_pairs([
+            ("tags", self.tags),
+            ("limit", &self.limit.to_string()),
+            ("pid", &self.pid.to_string()),
+        ]);
+        if let Some(credentials) = self.credentials {
+            target_url.query_pairs_mut().extend_pairs([
+                ("user_id", credentials.user_id.as_str()),
+                ("api_key", credentials.api_key.expose()),
+            ]);
+        }
+
+        let result = self.options.send_json(self.client.get(target_url)).await;
+        match self.credentials {
+            Some(_) => result.map_err(reqwest::Error::without_url),
+            None => result,
+        }
+    }
+}
+
+impl<'a> Getter<'a> {
+    /// Use another Gelbooru compatible site instead of [`url::BASE_URL`].
+    ///
+    /// The `base_url` is the `index.php` of the site, e.g. `https://safebooru.org/index.php`.
+    pub fn base_url(mut self, base_url: &'a Url) -> Self {
+        self.base_url = base_url;
+        self
+    }
+
+    /// Send authenticated requests with `credentials`.
+    pub fn credentials(mut self, credentials: &'a Credentials) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    /// Send the requests with `options`, default to [`RequestOptions::DEFAULT`].
+    pub fn options(mut self, options: &'a RequestOptions) -> Self {
+        self.options = options;
+        self
+    }
+}
+
+/// The [`Booru`] implementation for Gelbooru, which wraps [`Getter`].
+///
+/// `page` of [`Booru::search`] is the same as gelbooru's `pid`.
+#[non_exhaustive]
+#[derive(Debug, Clone)]
+pub struct Gelbooru {
+    /// The base URL of the Gelbooru compatible site, see [`Getter::base_url`].
+    pub base_url: Url,
+    /// The credentials for authenticated requests, see [`Getter::credentials`].
+    pub credentials: Option<Credentials>,
+    /// The options of the API requests, see [`Getter::options`].
+    pub options: RequestOptions,
+}
+
+impl Gelbooru {
+    /// The maximum number of posts in a single page.
+    pub const MAX_LIMIT: u64 = 100;
+
+    /// The maximum `limit * pid` that Gelbooru accepts.
+    pub const MAX_OFFSET: u64 = 20_000;
+
+    /// Create a backend for the Gelbooru compatible site at `base_url`.
+    pub fn new(base_url: Url) -> Self {
+        Self {
+            base_url,
+            credentials: None,
+            options: RequestOptions::DEFAULT,
+        }
+    }
+
+    /// Send authenticated requests with `credentials`, see [`Getter::credentials`].
+    pub fn with_credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    /// Send the API requests with `options`, see [`Getter::options`].
+    pub fn with_options(mut self, options: RequestOptions) -> Self {
+        self.options = options;
+        self
+    }
+}
+
+impl Default for Gelbooru {
+    fn default() -> Self {
+        Self::new(url::BASE.clone())
+    }
+}
+
+impl Booru for Gelbooru {
+    #[inline]
+    fn max_limit(&self) -> u64 {
+        Self::MAX_LIMIT
+    }
+
+    /// Gelbooru returns an error if `limit * pid > 20_000`.
+    ///
+    /// See: <https://gelbooru.com/index.php?page=forum&s=view&id=1549>
+    #[inline]
+    fn max_page(&self, limit: u64) -> Option<u64> {
+        Some(Self::MAX_OFFSET / limit)
+    }
+
+    fn search<'a>(
+        &'a self,
+        client: &'a Client,
+        tags: &'a str,
+        limit: u64,
+        page: u64,
+    ) -> impl Future<Output = reqwest::Result<Page>> + Send + 'a {
+        let getter = Getter::build(client, tags, limit, page)
+            .expect("illegal search arguments")
+            .base_url(&self.base_url)
+            .options(&self.options);
+        let getter = match &self.credentials {
+            Some(credentials) => getter.credentials(credentials),
+            None => getter,
+        };
+        async move {
+            let data = getter.run().await?;
+            let posts = data
+                .post
+                .unwrap_or_default()
+                .into_iter()
+                .map(Into::into)
+                .collect();
+            Ok(Page::new(
+                posts,
+                data.attributes.map(|attributes| attributes.count),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::num::{NonZeroU32, NonZeroU64, NonZeroUsize};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use futures::TryStreamExt;
+    use serde_json::json;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use crate::api::BatchGetter;
+    use crate::rate_limit::RateLimiter;
+    use crate::retry::RetryPolicy;
+
+    const COUNT: u64 = 150;
+
+    fn post_json(id: u64, tags: &str) -> serde_json::Value {
+        json!({
+            "id": id,
+            "md5": "9e107d9d372bb6826bd81d3542a419d6",
+            "file_url": format!("https://img3.gelbooru.com/images/9e/10/{id}.jpg"),
+            "tags": tags,
+            "image": "9e107d9d372bb6826bd81d3542a419d6.jpg",
+        })
+    }
+
+    /// A stand-in server with `COUNT` posts tagged with `cat`, and no post for other tags.
+    async fn mock_server() -> MockServer {
+        let server = MockServer::start().await;
+        for pid in 0..=COUNT / Gelbooru::MAX_LIMIT {
+            let start = pid * Gelbooru::MAX_LIMIT;
+            let end = std::cmp::min(start + Gelbooru::MAX_LIMIT, COUNT);
+            let posts: Vec<_> = (start..end).map(|id| post_json(id, "cat 1girl")).collect();
+            Mock::given(method("GET"))
+                .and(path("/index.php"))
+                .and(query_param("tags", "cat"))
+                .and(query_param("pid", pid.to_string()))
+                .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                    "@attributes": {"limit": posts.len(), "offset": start, "count": COUNT},
+                    "post": posts,
+                })))
+                .mount(&server)
+                .await;
+        }
+        Mock::given(method("GET"))
+            .and(path("/index.php"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "@attributes": {"limit": 100, "offset": 0, "count": 0},
+            })))
+            .with_priority(u8::MAX)
+            .mount(&server)
+            .await;
+        server
+    }
+
+    fn base_url(server: &MockServer) -> Url {
+        format!("{}/index.php", server.uri()).parse().unwrap()
+    }
+
+    #[test]
+    fn test_illegal_args() {
+        let client = Client::new();
+
+        let resp = Getter::build(&client, "", 100, 0);
+        assert!(resp.is_err());
+
+        let resp = Getter::build(&client, "cat", 0, 0);
+        assert!(resp.is_err());
+    }
+
+    #[test]
+    fn test_parse_legacy_json() {
+        let json = json!([{
+            "id": 1,
+            "hash": "9e107d9d372bb6826bd81d3542a419d6",
+            "file_url": "https://safebooru.org/images/1/9e107d9d372bb6826bd81d3542a419d6.png",
+            "tags": "cat",
+            "image": "9e107d9d372bb6826bd81d3542a419d6.png",
+            "rating": "safe",
+            "has_children": false,
+        }]);
+        let data: data::Json = serde_json::from_value(json).unwrap();
+        assert!(data.attributes.is_none());
+        let post: crate::api::data::field::Post = data.post.unwrap().remove(0).into();
+        assert_eq!(post.md5, "9e107d9d372bb6826bd81d3542a419d6");
+        assert_eq!(post.rating, Some(Rating::General));
+        assert_eq!(post.has_children, Some(false));
+        assert_eq!(post.score, None);
+
+        let data: data::Json = serde_json::from_value(json!([])).unwrap();
+        assert!(data.post.is_none());
+    }
+
+    #[test]
+    fn test_parse_metadata() {
+        let mut json = post_json(1, "cat");
+        json.as_object_mut().unwrap().extend(
+            json!({
+                "rating": "sensitive",
+                "score": 42,
+                "width": 1920,
+                "height": 1080,
+                "source": "",
+                "created_at": "Sat Aug 10 13:51:42 -0500 2024",
+                "owner": "danbooru",
+                "parent_id": 0,
+                "has_children": "true",
+                "sample_url": "",
+                "preview_url": "https://img3.gelbooru.com/thumbnails/9e/10/1.jpg",
+                "status": "active",
+            })
+            .as_object()
+            .unwrap()
+            .clone(),
+        );
+        let post: data::field::Post = serde_json::from_value(json).unwrap();
+        let post: crate::api::data::field::Post = post.into();
+        assert_eq!(post.rating, Some(Rating::Sensitive));
+        assert_eq!(post.score, Some(42));
+        assert_eq!((post.width, post.height), (Some(1920), Some(1080)));
+        assert_eq!(post.source, None);
+        assert_eq!(
+            post.created_at.as_deref(),
+            Some("Sat Aug 10 13:51:42 -0500 2024")
+        );
+        assert_eq!(post.owner.as_deref(), Some("danbooru"));
+        assert_eq!(post.parent_id, None);
+        assert_eq!(post.has_children, Some(true));
+        assert_eq!(post.sample_url, None);
+        assert_eq!(
+            post.preview_url.as_deref(),
+            Some("https://img3.gelbooru.com/thumbnails/9e/10/1.jpg")
+        );
+        assert_eq!(post.file_size, None);
+        assert_eq!(post.status.as_deref(), Some("active"));
+        assert_eq!(post.filename, PathBuf::from("1.jpg"));
+    }
+
+    #[tokio::test]
+    async fn test_get_api_data() -> reqwest::Result<()> {
+        let server = mock_server().await;
+        let base_url = base_url(&server);
+        let client = Client::new();
+        let tag = "cat";
+        let limit = 10;
+
+        let resp = Getter::build(&client, tag, limit, 0)
+            .unwrap()
+            .base_url(&base_url)
+            .run()
+            .await?;
+        assert_eq!(resp.attributes.unwrap().count, COUNT);
+        assert!(resp
+            .post
+            .expect("if `attributes.count` is correct, then `post` shouldn't be `None`")[0]
+            .tags
+            .contains(tag));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_retry_transient_errors() -> reqwest::Result<()> {
+        let server = mock_server().await;
+        // the first two requests fail, then the server recovers
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(503).insert_header("Retry-After", "0"))
+            .up_to_n_times(2)
+            .with_priority(1)
+            .mount(&server)
+            .await;
+        let base_url = base_url(&server);
+        let client = Client::new();
+        let retry = RetryPolicy::new(NonZeroU32::new(3).unwrap(), Duration::from_millis(1));
+        let options = RequestOptions::DEFAULT.retry(retry);
+
+        let resp = Getter::build(&client, "cat", 10, 0)
+            .unwrap()
+            .base_url(&base_url)
+            .options(&options)
+            .run()
+            .await?;
+        assert_eq!(resp.attributes.unwrap().count, COUNT);
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_request_rate() -> reqwest::Result<()> {
+        let server = mock_server().await;
+        let client = Client::new();
+        // the bucket holds 5 requests, so the 7th request waits for 0.4 second
+        let request_rate = Arc::new(RateLimiter::new(NonZeroU64::new(5).unwrap()));
+        let booru = Gelbooru::new(base_url(&server))
+            .with_options(RequestOptions::DEFAULT.request_rate(request_rate.clone()));
+        let another_booru = booru.clone();
+
+        let start = tokio::time::Instant::now();
+        for pid in 0..7 {
+            // the request rate is shared among the cloned backends
+            let booru = if pid % 2 == 0 { &booru } else { &another_booru };
+            booru.search(&client, "cat", 10, pid).await?;
+        }
+        assert!(start.elapsed() >= Duration::from_millis(400));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_credentials_not_leaked() {
+        const API_KEY: &str = "my_secret_api_key";
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(query_param("user_id", "42"))
+            .and(query_param("api_key", API_KEY))
+            .respond_with(ResponseTemplate::new(200).set_body_string("not json"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let client = Client::new();
+        let booru = Gelbooru::new(base_url(&server))
+            .with_credentials(Credentials::new("42", Secret::new(API_KEY)));
+
+        let err = booru
+            .search(&client, "cat", 10, 0)
+            .await
+            .expect_err("invalid json should be an error");
+        assert!(!format!("{err} {err:?}").contains(API_KEY));
+        assert!(!format!("{booru:?}").contains(API_KEY));
+    }
+
+    #[tokio::test]
+    async fn test_batch_get_api_data() -> reqwest::Result<()> {
+        let server = mock_server().await;
+        let client = Client::new();
+        let booru = Gelbooru::new(base_url(&server));
+        let tag = "cat";
+        let num_imgs = 101;
+
+        let resp = BatchGetter::build(&client, &booru, tag, num_imgs)
+            .unwrap()
+            .run()
+            .await?;
+        assert_eq!(resp.len(), usize::try_from(num_imgs).unwrap());
+
+        let tag = "balabala just no exist";
+        let resp = BatchGetter::build(&client, &booru, tag, num_imgs)
+            .unwrap()
+            .run()
+            .await?;
+        assert!(resp.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_batch_get_sliding_window() -> reqwest::Result<()> {
+        const PAGES: u64 = 4;
+        const SLOW_PID: u64 = 2;
+        const DELAY: Duration = Duration::from_secs(1);
+
+        let server = MockServer::start().await;
+        for pid in 0..PAGES {
+            let start = pid * Gelbooru::MAX_LIMIT;
+            let posts: Vec<_> = (start..start + Gelbooru::MAX_LIMIT)
+                .map(|id| post_json(id, "cat"))
+                .collect();
+            let response = ResponseTemplate::new(200).set_body_json(json!({
+                "@attributes": {"limit": posts.len(), "offset": start, "count": PAGES * Gelbooru::MAX_LIMIT},
+                "post": posts,
+            }));
+            let response = if pid == SLOW_PID {
+                response.set_delay(DELAY)
+            } else {
+                response
+            };
+            Mock::given(method("GET"))
+                .and(query_param("pid", pid.to_string()))
+                .respond_with(response)
+                .mount(&server)
+                .await;
+        }
+        let client = Client::new();
+        let booru = Gelbooru::new(base_url(&server));
+
+        let start = tokio::time::Instant::now();
+        let stream = BatchGetter::build(&client, &booru, "cat", PAGES * Gelbooru::MAX_LIMIT)
+            .unwrap()
+            .concurrency(NonZeroUsize::new(3).unwrap())
+            .stream();
+        let mut stream = std::pin::pin!(stream);
+        let mut arrivals = Vec::new();
+        while let Some(posts) = stream.try_next().await? {
+            arrivals.push((posts[0].id, start.elapsed()));
+        }
+
+        let ids: Vec<_> = arrivals.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, [0, 100, 200, 300]);
+        // the page before the slow one is yielded without waiting for it
+        assert!(arrivals[1].1 < DELAY / 2);
+        assert!(arrivals[2].1 >= DELAY);
+        // the page after the slow one was fetched concurrently, so it doesn't wait for another delay
+        assert!(arrivals[3].1 < DELAY * 3 / 2);
+        Ok(())
+    }
+}
@@ -0,0 +1,951 @@
+//! A core module for interacting with the booru APIs.
+//!
+//! Every booru backend implements the [`Booru`] trait,
+//! which maps the site's response into the common [`data::field::Post`].
+//!
+//! Usually, you prefer to use the [`BatchGetter`] struct to get the [`data`] from a [`Booru`].
+//!
+//! Currently supported backends:
+//! - [`gelbooru`]
+//! - [`danbooru`]
+//! - [`moebooru`]
+
+use std::fmt;
+use std::future::Future;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use futures::stream::BoxStream;
+use futures::{future, stream, Stream, StreamExt, TryStreamExt};
+use reqwest::{Client, RequestBuilder, Url};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::filter::{Blacklist, FilterStats, PostFilter};
+use crate::rate_limit::RateLimiter;
+use crate::retry::RetryPolicy;
+
+pub mod danbooru;
+pub mod gelbooru;
+pub mod moebooru;
+
+pub use danbooru::Danbooru;
+pub use gelbooru::Gelbooru;
+pub use moebooru::Moebooru;
+
+/// The common data structure of all [`Booru`] backends.
+pub mod data {
+    use super::*;
+
+    /// The fields of the common data.
+    pub mod field {
+        use super::*;
+
+        /// The content rating of a [`Post`].
+        ///
+        /// The ratings of the boorus are normalized into the Danbooru's convention,
+        /// e.g. `safe` of Moebooru and Gelbooru 0.2 is [`Self::General`].
+        #[non_exhaustive]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+        #[serde(rename_all = "lowercase")]
+        pub enum Rating {
+            /// Safe for work.
+            General,
+            /// Slightly suggestive.
+            Sensitive,
+            /// Moderately explicit.
+            Questionable,
+            /// Explicit.
+            Explicit,
+        }
+
+        /// The common post, which is mapped from the response of a [`Booru`].
+        ///
+        /// The metadata fields are `None` if the booru doesn't return them.
+        #[non_exhaustive]
+        #[derive(Debug, Clone, Serialize)]
+        pub struct Post {
+            /// The ID of the image.
+            pub id: u64,
+            /// The MD5 hash of the image.
+            pub md5: String,
+            /// The URL of the image, which can be used to download the image.
+            pub file_url: String,
+            /// The tags of the image, separated by spaces. Note: these tags are marked by the booru.
+            pub tags: String,
+            /// The original file name of the image.
+            pub image: PathBuf,
+            /// The filename of the image, which is the same as `id` with the extension of `image`.
+            /// We will use this field to save the image.
+            pub(crate) filename: PathBuf,
+            /// The content rating of the image.
+            pub rating: Option<Rating>,
+            /// The score of the image, voted by the users.
+            pub score: Option<i64>,
+            /// The width of the image in pixels.
+            pub width: Option<u64>,
+            /// The height of the image in pixels.
+            pub height: Option<u64>,
+            /// The source of the image, usually an URL. `None` if empty.
+            pub source: Option<String>,
+            /// The creation time of the post, in the booru's own format,
+            /// e.g. `Sat Aug 10 13:51:42 -0500 2024` for Gelbooru,
+            /// ISO 8601 for Danbooru, or the Unix timestamp for Moebooru.
+            pub created_at: Option<String>,
+            /// The name of the uploader.
+            pub owner: Option<String>,
+            /// The ID of the parent post.
+            pub parent_id: Option<u64>,
+            /// Whether the post has child posts.
+            pub has_children: Option<bool>,
+            /// The URL of the resized sample image.
+            pub sample_url: Option<String>,
+            /// The URL of the thumbnail.
+            pub preview_url: Option<String>,
+            /// The file size of the image in bytes.
+            pub file_size: Option<u64>,
+            /// The moderation status of the post, e.g. `active`, `pending` or `deleted`.
+            pub status: Option<String>,
+        }
+
+        impl Post {
+            /// `filename` equals to `id` with `image`'s extension.
+            /// e.g. `id = 12345`, `image = "test.jpg"`, then `filename = "12345.jpg"`.
+            ///
+            /// All the metadata fields are `None`, set them if available.
+            ///
+            /// # Panics
+            ///
+            /// If `image` has no file name, e.g. `..`.
+            pub fn new(
+                id: u64,
+                md5: String,
+                file_url: String,
+                tags: String,
+                image: PathBuf,
+            ) -> Self {
+                use crate::tool::SetFileStem;
+
+                // make sure only the filename is retained
+                let mut filename: PathBuf = image.file_name().unwrap().into();
+                filename.set_file_stem(id.to_string());
+
+                Self {
+                    id,
+                    md5,
+                    file_url,
+                    tags,
+                    image,
+                    filename,
+                    rating: None,
+                    score: None,
+                    width: None,
+                    height: None,
+                    source: None,
+                    created_at: None,
+                    owner: None,
+                    parent_id: None,
+                    has_children: None,
+                    sample_url: None,
+                    preview_url: None,
+                    file_size: None,
+                    status: None,
+                }
+            }
+        }
+    }
+}
+
+/// A single page of the search result from a [`Booru`].
+#[non_exhaustive]
+#[derive(Debug)]
+pub struct Page {
+    /// The posts of this page.
+    pub posts: Vec<data::field::Post>,
+    /// The total number of posts matching the tags,
+    /// if the booru returns it along with the page.
+    pub count: Option<u64>,
+    /// Whether the booru returned nothing for this page, i.e. there are no more posts.
+    ///
+    /// Note: `posts` may be empty even if not exhausted,
+    /// e.g. all posts of this page were skipped by the backend.
+    pub exhausted: bool,
+}
+
+impl Page {
+    /// Create a page, which is [`exhausted`](Self::exhausted) if `posts` is empty.
+    pub fn new(posts: Vec<data::field::Post>, count: Option<u64>) -> Self {
+        let exhausted = posts.is_empty();
+        Self {
+            posts,
+            count,
+            exhausted,
+        }
+    }
+
+    /// Set whether the booru has no more posts, see [`Self::exhausted`].
+    pub fn with_exhausted(mut self, exhausted: bool) -> Self {
+        self.exhausted = exhausted;
+        self
+    }
+}
+
+/// The options of the API requests, which are held by every [`Booru`] backend
+/// and passed to its low-level `Getter`.
+///
+/// # Example
+///
+/// ```rust
+/// use std::num::NonZeroU32;
+/// use std::time::Duration;
+///
+/// use booru_dl::api::{Gelbooru, RequestOptions};
+/// use booru_dl::retry::RetryPolicy;
+///
+/// let retry = RetryPolicy::new(NonZeroU32::new(5).unwrap(), Duration::from_secs(1));
+/// let booru = Gelbooru::default().with_options(RequestOptions::DEFAULT.retry(retry));
+/// ```
+#[non_exhaustive]
+#[derive(Debug, Clone)]
+pub struct RequestOptions {
+    /// The retry policy for the transient errors.
+    pub retry: RetryPolicy,
+    /// The request rate limiter, each attempt acquires a token from it.
+    pub request_rate: Option<Arc<RateLimiter>>,
+}
+
+impl RequestOptions {
+    /// The default options, which retry with [`RetryPolicy::DEFAULT`]
+    /// and don't limit the request rate.
+    pub const DEFAULT: Self = Self {
+        retry: RetryPolicy::DEFAULT,
+        request_rate: None,
+    };
+
+    /// Retry the transient errors with `retry` policy.
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Limit the request rate with `request_rate`.
+    ///
+    /// Share the same `request_rate` among the backends to limit their total request rate.
+    pub fn request_rate(mut self, request_rate: Arc<RateLimiter>) -> Self {
+        self.request_rate = Some(request_rate);
+        self
+    }
+
+    /// Send the `request` with these options, and parse the JSON response.
+    async fn send_json<T: DeserializeOwned>(&self, request: RequestBuilder) -> reqwest::Result<T> {
+        self.retry
+            .send_json(request, self.request_rate.as_deref())
+            .await
+    }
+}
+
+impl Default for RequestOptions {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// A booru backend, which can search posts from the booru API.
+pub trait Booru {
+    /// The maximum number of posts in a single page.
+    fn max_limit(&self) -> u64;
+
+    /// Search a single page of posts.
+    ///
+    /// `page` is zero-based, the implementor will convert it to the booru's own convention.
+    ///
+    /// # Panics
+    ///
+    /// If `tags` is empty, or `limit` is not in the range `1..=max_limit`, this function may panic.
+    fn search<'a>(
+        &'a self,
+        client: &'a Client,
+        tags: &'a str,
+        limit: u64,
+        page: u64,
+    ) -> impl Future<Output = reqwest::Result<Page>> + Send + 'a;
+
+    /// The maximum zero-based `page` that the booru accepts with `limit`,
+    /// `None` means there is no such limit.
+    ///
+    /// Beyond this page, [`BatchGetter`] will switch to id-cursor pagination,
+    /// i.e. append `id:<LAST_ID` to the tags and restart from page 0,
+    /// so the booru must support the `id:<N` metatag and sort posts by id descending by default.
+    ///
+    /// The default implementation returns `None`.
+    fn max_page(&self, _limit: u64) -> Option<u64> {
+        None
+    }
+
+    /// Get the total number of posts matching the tags.
+    ///
+    /// [`BatchGetter`] only calls this when [`Page::count`] is `None`.
+    /// The default implementation returns `None`, which means the count is unknown.
+    fn count<'a>(
+        &'a self,
+        _client: &'a Client,
+        _tags: &'a str,
+    ) -> impl Future<Output = reqwest::Result<Option<u64>>> + Send + 'a {
+        async { Ok(None) }
+    }
+}
+
+/// The booru sites that can be chosen at runtime, e.g. from [`crate::config::Config`].
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Site {
+    /// See [`Gelbooru`].
+    #[default]
+    Gelbooru,
+    /// See [`Danbooru`].
+    Danbooru,
+    /// See [`Moebooru::yandere`].
+    Yandere,
+    /// See [`Moebooru::konachan`].
+    Konachan,
+}
+
+impl Site {
+    /// Create the backend of this site for another instance at `base_url`,
+    /// e.g. a Gelbooru compatible site or a self-hosted mirror.
+    ///
+    /// See the `new` function of each backend for the format of `base_url`.
+    pub fn with_base_url(self, base_url: Url) -> AnyBooru {
+        match self {
+            Self::Gelbooru => Gelbooru::new(base_url).into(),
+            Self::Danbooru => Danbooru::new(base_url).into(),
+            Self::Yandere | Self::Konachan => Moebooru::new(base_url).into(),
+        }
+    }
+}
+
+impl fmt::Display for Site {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Gelbooru => f.write_str("Gelbooru"),
+            Self::Danbooru => f.write_str("Danbooru"),
+            Self::Yandere => f.write_str("yande.re"),
+            Self::Konachan => f.write_str("Konachan"),
+        }
+    }
+}
+
+/// A [`Booru`] backend chosen at runtime.
+///
+/// Use [`From<Site>`] to create the backend with its default settings.
+#[non_exhaustive]
+#[derive(Debug, Clone)]
+pub enum AnyBooru {
+    /// See [`Gelbooru`].
+    Gelbooru(Gelbooru),
+    /// See [`Danbooru`].
+    Danbooru(Danbooru),
+    /// See [`Moebooru`].
+    Moebooru(Moebooru),
+}
+
+impl From<Gelbooru> for AnyBooru {
+    fn from(value: Gelbooru) -> Self {
+        Self::Gelbooru(value)
+    }
+}
+
+impl From<Danbooru> for AnyBooru {
+    fn from(value: Danbooru) -> Self {
+        Self::Danbooru(value)
+    }
+}
+
+impl From<Moebooru> for AnyBooru {
+    fn from(value: Moebooru) -> Self {
+        Self::Moebooru(value)
+    }
+}
+
+impl From<Site> for AnyBooru {
+    fn from(value: Site) -> Self {
+        match value {
+            Site::Gelbooru => Gelbooru::default().into(),
+            Site::Danbooru => Danbooru::default().into(),
+            Site::Yandere => Moebooru::yandere().into(),
+            Site::Konachan => Moebooru::konachan().into(),
+        }
+    }
+}
+
+impl AnyBooru {
+    /// The [`RequestOptions`] of the wrapped backend.
+    fn options_mut(&mut self) -> &mut RequestOptions {
+        match self {
+            Self::Gelbooru(booru) => &mut booru.options,
+            Self::Danbooru(booru) => &mut booru.options,
+            Self::Moebooru(booru) => &mut booru.options,
+        }
+    }
+
+    /// Retry the API requests with `retry` policy, see [`RequestOptions::retry`].
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.options_mut().retry = retry;
+        self
+    }
+
+    /// Limit the request rate of the API requests, see [`RequestOptions::request_rate`].
+    pub fn with_request_rate(mut self, request_rate: Arc<RateLimiter>) -> Self {
+        self.options_mut().request_rate = Some(request_rate);
+        self
+    }
+}
+
+impl Booru for AnyBooru {
+    fn max_limit(&self) -> u64 {
+        match self {
+            Self::Gelbooru(booru) => booru.max_limit(),
+            Self::Danbooru(booru) => booru.max_limit(),
+            Self::Moebooru(booru) => booru.max_limit(),
+        }
+    }
+
+    async fn search<'a>(
+        &'a self,
+        client: &'a Client,
+        tags: &'a str,
+        limit: u64,
+        page: u64,
+    ) -> reqwest::Result<Page> {
+        match self {
+            Self::Gelbooru(booru) => booru.search(client, tags, limit, page).await,
+            Self::Danbooru(booru) => booru.search(client, tags, limit, page).await,
+            Self::Moebooru(booru) => booru.search(client, tags, limit, page).await,
+        }
+    }
+
+    async fn count<'a>(
+        &'a self,
+        client: &'a Client,
+        tags: &'a str,
+    ) -> reqwest::Result<Option<u64>> {
+        match self {
+            Self::Gelbooru(booru) => booru.count(client, tags).await,
+            Self::Danbooru(booru) => booru.count(client, tags).await,
+            Self::Moebooru(booru) => booru.count(client, tags).await,
+        }
+    }
+}
+
+/// This helper wraps a [`Booru`] and automatically polls the API until the number of images is reached.
+///
+/// # Example
+///
+/// ```rust
+/// use reqwest::Client;
+/// use booru_dl::api::{BatchGetter, Gelbooru};
+///
+/// #[tokio::main]
+/// async fn main() -> reqwest::Result<()> {
+///     let client = Client::new();
+///     let booru = Gelbooru::default();
+///
+///     let posts = BatchGetter::build(&client, &booru, "cat", 10)
+///         .expect("illegal arguments")
+///         .run()
+///         .await?;
+///
+///     Ok(())
+/// }
+/// ```
+pub struct BatchGetter<'a, B> {
+    client: &'a Client,
+    booru: &'a B,
+    tags: &'a str,
+    num_imgs: u64,
+    concurrency: NonZeroUsize,
+    filter: Option<&'a PostFilter>,
+    blacklist: Option<&'a Blacklist>,
+    filter_stats: Option<&'a FilterStats>,
+}
+
+impl<B: Booru> BatchGetter<'_, B> {
+    /// The default number of pages to fetch concurrently, which is small to be polite to the booru.
+    pub const DEFAULT_CONCURRENCY: NonZeroUsize = match NonZeroUsize::new(2) {
+        Some(concurrency) => concurrency,
+        None => unreachable!(),
+    };
+
+    /// See [`Booru::search`] for arguments.
+    ///
+    /// # Errors
+    ///
+    /// If `tags` is empty, or `num_imgs` is 0, this function will return an error.
+    pub fn build<'a>(
+        client: &'a Client,
+        booru: &'a B,
+        tags: &'a str,
+        num_imgs: u64,
+    ) -> anyhow::Result<BatchGetter<'a, B>> {
+        if tags.is_empty() {
+            return Err(anyhow::anyhow!("Tags cannot be empty"));
+        }
+        if num_imgs == 0 {
+            return Err(anyhow::anyhow!("Number of images cannot be 0"));
+        }
+        Ok(BatchGetter {
+            client,
+            booru,
+            tags,
+            num_imgs,
+            concurrency: Self::DEFAULT_CONCURRENCY,
+            filter: None,
+            blacklist: None,
+            filter_stats: None,
+        })
+    }
+
+    /// Set the maximum number of pages to fetch concurrently,
+    /// default to [`Self::DEFAULT_CONCURRENCY`].
+    ///
+    /// Pages are fetched concurrently only after the total number of posts is known,
+    /// see [`Booru::count`].
+    pub fn concurrency(mut self, concurrency: NonZeroUsize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+}
+
+impl<'a, B: Booru> BatchGetter<'a, B> {
+    /// Drop the posts rejected by `filter`, so that `num_imgs` counts the posts that pass it.
+    pub fn filter(mut self, filter: &'a PostFilter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Drop the posts with any tag matching `blacklist`,
+    /// so that `num_imgs` counts the posts that pass it.
+    pub fn blacklist(mut self, blacklist: &'a Blacklist) -> Self {
+        self.blacklist = Some(blacklist);
+        self
+    }
+
+    /// Count the posts rejected by [`Self::filter`] and [`Self::blacklist`] into `filter_stats`.
+    pub fn filter_stats(mut self, filter_stats: &'a FilterStats) -> Self {
+        self.filter_stats = Some(filter_stats);
+        self
+    }
+}
+
+/// The state of [`BatchGetter::stream`].
+struct StreamState<'a> {
+    /// The next zero-based page to request.
+    page: u64,
+    /// The number of posts still needed, `None` before the first page is fetched.
+    remaining: Option<usize>,
+    /// Whether the total number of posts is known, see [`Booru::count`].
+    count_known: bool,
+    /// Whether the booru has no more posts.
+    exhausted: bool,
+    /// The smallest id fetched so far, used as the cursor of id-cursor pagination.
+    min_id: Option<u64>,
+    /// `Some` if we have switched to id-cursor pagination.
+    cursor_tags: Option<String>,
+    /// The pages being fetched concurrently, which are yielded in page order.
+    window: Option<BoxStream<'a, reqwest::Result<Page>>>,
+}
+
+impl<'a, B: Booru + Sync> BatchGetter<'a, B> {
+    /// Wraps the [`Booru::search`] and automatically polls the API until the number of images is reached,
+    /// yielding the posts page by page as soon as each page arrives.
+    ///
+    /// Empty pages are never yielded, so the stream yields nothing if none of the images are found.
+    /// This includes the pages whose posts are all rejected by [`Self::filter`] or [`Self::blacklist`],
+    /// in which case the stream keeps polling the following pages.
+    ///
+    /// If the total number of posts is unknown (see [`Booru::count`]),
+    /// the stream will keep polling until an empty page is returned.
+    ///
+    /// If the pages are beyond [`Booru::max_page`], the stream will transparently
+    /// switch to id-cursor pagination, so `num_imgs` can be arbitrarily large.
+    ///
+    /// Once the total number of posts is known, the remaining pages are fetched concurrently
+    /// in a sliding window (see [`Self::concurrency`]): each page is yielded as soon as it
+    /// and the pages before it arrive, while the following pages are still being fetched.
+    ///
+    /// # Errors
+    ///
+    /// If the request fails, the stream will yield the error and then terminate.
+    ///
+    /// <div class="warning">
+    ///
+    /// The id-cursor pagination relies on the default sort order (id descending),
+    /// don't use `sort:` metatags in `tags` if `num_imgs` is beyond [`Booru::max_page`].
+    ///
+    /// </div>
+    pub fn stream(self) -> impl Stream<Item = reqwest::Result<Vec<data::field::Post>>> + Send + 'a {
+        let Self {
+            client,
+            booru,
+            tags,
+            num_imgs,
+            concurrency,
+            filter,
+            blacklist,
+            filter_stats,
+        } = self;
+        let passes = move |post: &data::field::Post| {
+            let checked = filter
+                .map_or(Ok(()), |filter| filter.check(post))
+                .and_then(|()| blacklist.map_or(Ok(()), |blacklist| blacklist.check(post)));
+            let Err(reason) = checked else {
+                return true;
+            };
+            if let Some(filter_stats) = filter_stats {
+                filter_stats.record(reason);
+            }
+            false
+        };
+        let limit = booru.max_limit();
+        let max_page = booru.max_page(limit);
+
+        let state = StreamState {
+            page: 0,
+            remaining: None,
+            count_known: false,
+            exhausted: false,
+            min_id: None,
+            cursor_tags: None,
+            window: None,
+        };
+        // Each step yields the posts of a page.
+        stream::try_unfold(state, move |mut state| async move {
+            loop {
+                if state.exhausted || state.remaining == Some(0) {
+                    // dropping the window cancels the requests of the unneeded pages
+                    return Ok(None);
+                }
+
+                let window = match &mut state.window {
+                    Some(window) => window,
+                    None => {
+                        if max_page.is_some_and(|max_page| state.page > max_page) {
+                            // Posts are sorted by id descending, so all posts with smaller ids have not been fetched yet.
+                            // If no post is fetched yet, there is nothing to do with the cursor.
+                            let Some(last_id) = state.min_id else {
+                                return Ok(None);
+                            };
+                            state.cursor_tags = Some(format!("{tags} id:<{last_id}"));
+                            state.page = 0;
+                        }
+                        let pages = match state.remaining {
+                            // Without the count, we don't know how many pages are left,
+                            // so we fetch them one by one to avoid useless requests.
+                            Some(remaining) if state.count_known => {
+                                let needed_pages =
+                                    u64::try_from(remaining).unwrap().div_ceil(limit);
+                                let window_pages =
+                                    max_page.map_or(u64::MAX, |max_page| max_page - state.page + 1);
+                                needed_pages.min(window_pages)
+                            }
+                            _ => 1,
+                        };
+                        let current_tags = state.cursor_tags.clone();
+                        let window = stream::iter(state.page..state.page + pages)
+                            .map(move |page| {
+                                let current_tags = current_tags.clone();
+                                async move {
+                                    let current_tags = current_tags.as_deref().unwrap_or(tags);
+                                    booru.search(client, current_tags, limit, page).await
+                                }
+                            })
+                            .buffered(concurrency.get())
+                            .boxed();
+                        state.page += pages;
+                        state.window.insert(window)
+                    }
+                };
+                // The window is drained, but more posts are needed, e.g. some posts were filtered,
+                // so open the next window.
+                let Some(page) = window.try_next().await? else {
+                    state.window = None;
+                    continue;
+                };
+
+                // no more posts, maybe the count is unknown or changed during polling
+                if page.exhausted {
+                    state.exhausted = true;
+                    continue;
+                }
+
+                let remaining = match state.remaining {
+                    Some(remaining) => remaining,
+                    None => {
+                        let count = match page.count {
+                            Some(count) => Some(count),
+                            None => booru.count(client, tags).await?,
+                        };
+                        state.count_known = count.is_some();
+                        count
+                            .map_or(num_imgs, |count| std::cmp::min(num_imgs, count))
+                            .try_into()
+                            .expect("total number is too large to convert to `usize`")
+                    }
+                };
+
+                let mut posts = Vec::with_capacity(std::cmp::min(remaining, page.posts.len()));
+                for post in page.posts {
+                    if posts.len() == remaining {
+                        break;
+                    }
+                    // The rejected posts also move the cursor, or they would be fetched again.
+                    state.min_id = Some(state.min_id.map_or(post.id, |min_id| min_id.min(post.id)));
+                    if passes(&post) {
+                        posts.push(post);
+                    }
+                }
+                state.remaining = Some(remaining - posts.len());
+                return Ok(Some((posts, state)));
+            }
+        })
+        .try_filter(|posts| future::ready(!posts.is_empty()))
+    }
+
+    /// Collect all posts of [`Self::stream`] into a vector.
+    ///
+    /// If none of the images are found, this function will return an zero capacity vector.
+    ///
+    /// # Errors
+    ///
+    /// If the request fails, this function will return an error.
+    ///
+    /// <div class="warning">
+    ///
+    /// See the warning of [`Self::stream`].
+    ///
+    /// </div>
+    pub async fn run(self) -> reqwest::Result<Vec<data::field::Post>> {
+        self.stream().try_concat().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use crate::filter::FilterReason;
+
+    /// A fake booru with `count` posts sorted by id descending.
+    struct FakeBooru {
+        count: u64,
+        /// See [`Booru::max_page`].
+        max_page: Option<u64>,
+        /// Whether to report the count along with the page.
+        report_count: bool,
+        /// The number of concurrent searches, and its maximum value.
+        in_flight: AtomicUsize,
+        max_in_flight: AtomicUsize,
+    }
+
+    impl FakeBooru {
+        fn new(count: u64, max_page: Option<u64>, report_count: bool) -> Self {
+            Self {
+                count,
+                max_page,
+                report_count,
+                in_flight: AtomicUsize::new(0),
+                max_in_flight: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    impl Booru for FakeBooru {
+        fn max_limit(&self) -> u64 {
+            10
+        }
+
+        fn max_page(&self, _limit: u64) -> Option<u64> {
+            self.max_page
+        }
+
+        async fn search<'a>(
+            &'a self,
+            _client: &'a Client,
+            tags: &'a str,
+            limit: u64,
+            page: u64,
+        ) -> reqwest::Result<Page> {
+            if self.max_page.is_some_and(|max_page| page > max_page) {
+                panic!("page {page} is out of range");
+            }
+            let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+            let cursor = tags
+                .split(' ')
+                .find_map(|tag| tag.strip_prefix("id:<"))
+                .map_or(u64::MAX, |id| id.parse().unwrap());
+            let posts = (0..self.count)
+                .rev()
+                .filter(|id| *id < cursor)
+                .skip((limit * page).try_into().unwrap())
+                .take(limit.try_into().unwrap())
+                .map(|id| {
+                    let mut post = data::field::Post::new(
+                        id,
+                        String::new(),
+                        String::new(),
+                        String::from("cat"),
+                        PathBuf::from("image.jpg"),
+                    );
+                    // the odd posts have a higher score, so that we can filter them
+                    post.score = Some(i64::try_from(id % 2).unwrap());
+                    post
+                })
+                .collect();
+            let count = self.report_count.then_some(self.count);
+            Ok(Page::new(posts, count))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch_get_fake_data() -> reqwest::Result<()> {
+        let client = Client::new();
+        let booru = FakeBooru::new(25, None, false);
+
+        let resp = BatchGetter::build(&client, &booru, "cat", 21)
+            .unwrap()
+            .run()
+            .await?;
+        assert_eq!(resp.len(), 21);
+        assert_eq!(resp[20].filename, PathBuf::from("4.jpg"));
+
+        // stop at the empty page
+        let resp = BatchGetter::build(&client, &booru, "cat", 100)
+            .unwrap()
+            .run()
+            .await?;
+        assert_eq!(resp.len(), 25);
+
+        let booru = FakeBooru::new(0, None, false);
+        let resp = BatchGetter::build(&client, &booru, "cat", 100)
+            .unwrap()
+            .run()
+            .await?;
+        assert!(resp.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_batch_get_stream() -> reqwest::Result<()> {
+        let client = Client::new();
+        let booru = FakeBooru::new(25, None, false);
+
+        let pages: Vec<_> = BatchGetter::build(&client, &booru, "cat", 21)
+            .unwrap()
+            .stream()
+            .map_ok(|posts| posts.len())
+            .try_collect()
+            .await?;
+        assert_eq!(pages, [10, 10, 1]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_batch_get_beyond_max_page() -> reqwest::Result<()> {
+        let client = Client::new();
+        // only 20 posts can be reached by paging
+        let booru = FakeBooru::new(55, Some(1), false);
+
+        let resp = BatchGetter::build(&client, &booru, "cat", 50)
+            .unwrap()
+            .run()
+            .await?;
+        let ids: Vec<u64> = resp.iter().map(|post| post.id).collect();
+        assert_eq!(ids, (5..55).rev().collect::<Vec<_>>());
+
+        let resp = BatchGetter::build(&client, &booru, "cat", 100)
+            .unwrap()
+            .run()
+            .await?;
+        assert_eq!(resp.len(), 55);
+
+        // id-cursor pagination with concurrency
+        let booru = FakeBooru::new(55, Some(1), true);
+        let resp = BatchGetter::build(&client, &booru, "cat", 50)
+            .unwrap()
+            .run()
+            .await?;
+        let ids: Vec<u64> = resp.iter().map(|post| post.id).collect();
+        assert_eq!(ids, (5..55).rev().collect::<Vec<_>>());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_batch_get_filtered() -> reqwest::Result<()> {
+        let client = Client::new();
+        let filter = PostFilter {
+            min_score: Some(1),
+            ..Default::default()
+        };
+        // only 10 posts can be reached by paging, the rest are fetched by id-cursor
+        let booru = FakeBooru::new(55, Some(0), false);
+
+        let filter_stats = FilterStats::default();
+        let resp = BatchGetter::build(&client, &booru, "cat", 20)
+            .unwrap()
+            .filter(&filter)
+            .filter_stats(&filter_stats)
+            .run()
+            .await?;
+        let ids: Vec<u64> = resp.iter().map(|post| post.id).collect();
+        assert_eq!(ids, (15..54).rev().step_by(2).collect::<Vec<_>>());
+        assert_eq!(filter_stats.get(FilterReason::Score), 20);
+
+        // stop at the empty page, even if `num_imgs` is not reached
+        let resp = BatchGetter::build(&client, &booru, "cat", 100)
+            .unwrap()
+            .filter(&filter)
+            .run()
+            .await?;
+        assert_eq!(resp.len(), 27);
+
+        // the blacklist is counted separately
+        let blacklist = Blacklist::new(["c*t"]).unwrap();
+        let filter_stats = FilterStats::default();
+        let resp = BatchGetter::build(&client, &booru, "cat", 100)
+            .unwrap()
+            .filter(&filter)
+            .blacklist(&blacklist)
+            .filter_stats(&filter_stats)
+            .run()
+            .await?;
+        assert!(resp.is_empty());
+        assert_eq!(filter_stats.get(FilterReason::Score), 28);
+        assert_eq!(filter_stats.get(FilterReason::Blacklist), 27);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_batch_get_concurrently() -> reqwest::Result<()> {
+        let client = Client::new();
+        let booru = FakeBooru::new(100, None, true);
+
+        let resp = BatchGetter::build(&client, &booru, "cat", 95)
+            .unwrap()
+            .concurrency(NonZeroUsize::new(4).unwrap())
+            .run()
+            .await?;
+        let ids: Vec<u64> = resp.iter().map(|post| post.id).collect();
+        assert_eq!(ids, (5..100).rev().collect::<Vec<_>>());
+        assert_eq!(booru.max_in_flight.load(Ordering::SeqCst), 4);
+        Ok(())
+    }
+}
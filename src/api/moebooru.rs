@@ -0,0 +1,422 @@
+//! The [Moebooru](https://github.com/moebooru/moebooru) backend,
+//! which is used by [yande.re](https://yande.re/) and [Konachan](https://konachan.com/).
+//!
+//! Usually, you prefer to use [`Moebooru`] with [`crate::api::BatchGetter`],
+//! [`Getter`] is the low-level way to get the raw [`data`] from the Moebooru API.
+
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+
+use super::data::field::Rating;
+use super::{Booru, Page, RequestOptions};
+
+/// The URLs for the Moebooru API.
+pub mod url {
+    use super::*;
+
+    /// The base URL of the yande.re.
+    pub const YANDERE_BASE_URL: &str = "https://yande.re/";
+
+    /// The base URL of the Konachan.
+    pub const KONACHAN_BASE_URL: &str = "https://konachan.com/";
+
+    /// The path of the posts API, relative to the base URL.
+    ///
+    /// See: <https://yande.re/help/api>
+    pub const POSTS_PATH: &str = "post.json";
+
+    /// The parsed [`YANDERE_BASE_URL`].
+    pub static YANDERE_BASE: LazyLock<Url> =
+        LazyLock::new(|| Url::parse(YANDERE_BASE_URL).unwrap());
+
+    /// The parsed [`KONACHAN_BASE_URL`].
+    pub static KONACHAN_BASE: LazyLock<Url> =
+        LazyLock::new(|| Url::parse(KONACHAN_BASE_URL).unwrap());
+}
+
+/// The data structure for the JSON response from the Moebooru API.
+pub mod data {
+    use super::*;
+
+    /// The fields of the JSON response.
+    pub mod field {
+        use super::*;
+
+        /// The post of the posts API.
+        ///
+        /// This is the raw post of Moebooru,
+        /// it can be converted into the common [`crate::api::data::field::Post`]
+        /// only if `file_url` is present.
+        #[non_exhaustive]
+        #[derive(Debug, Deserialize, Serialize)]
+        pub struct Post {
+            /// The ID of the image.
+            pub id: u64,
+            /// The MD5 hash of the image.
+            pub md5: String,
+            /// The URL of the original image.
+            /// `None` if the post is deleted.
+            pub file_url: Option<String>,
+            /// The URL of the `jpeg` version of the image, if the original is a `png`.
+            pub jpeg_url: Option<String>,
+            /// The URL of the resized sample image.
+            pub sample_url: Option<String>,
+            /// The extension of the image, e.g. `jpg`.
+            /// Old Moebooru versions don't return this field.
+            pub file_ext: Option<String>,
+            /// The tags of the image, separated by spaces.
+            pub tags: String,
+            /// The rating, one of `s`, `q` or `e`.
+            pub rating: Option<String>,
+            /// The score of the image.
+            pub score: Option<i64>,
+            /// The width of the image in pixels.
+            pub width: Option<u64>,
+            /// The height of the image in pixels.
+            pub height: Option<u64>,
+            /// The source of the image, may be empty.
+            pub source: Option<String>,
+            /// The creation time as the Unix timestamp.
+            pub created_at: Option<i64>,
+            /// The name of the uploader.
+            pub author: Option<String>,
+            /// The ID of the parent post.
+            pub parent_id: Option<u64>,
+            /// Whether the post has child posts.
+            pub has_children: Option<bool>,
+            /// The URL of the thumbnail.
+            pub preview_url: Option<String>,
+            /// The file size of the original image in bytes.
+            pub file_size: Option<u64>,
+            /// The moderation status, e.g. `active`, `pending` or `deleted`.
+            pub status: Option<String>,
+        }
+    }
+}
+
+impl TryFrom<data::field::Post> for super::data::field::Post {
+    /// The raw post is returned if it is deleted.
+    type Error = data::field::Post;
+
+    /// The `image` is `{md5}.{file_ext}`, if `file_ext` is absent,
+    /// the extension of `file_url` is used instead.
+    /// The empty `source` is mapped to `None`, and `created_at` is kept as the Unix timestamp.
+    fn try_from(value: data::field::Post) -> Result<Self, Self::Error> {
+        let Some(file_url) = value.file_url.clone() else {
+            return Err(value);
+        };
+
+        let file_ext = match &value.file_ext {
+            Some(file_ext) => file_ext.clone(),
+            None => Path::new(file_url.rsplit('/').next().unwrap_or_default())
+                .extension()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned(),
+        };
+        let image = PathBuf::from(format!("{}.{file_ext}", value.md5));
+
+        let rating = value.rating.as_deref().and_then(|rating| match rating {
+            "s" => Some(Rating::General),
+            "q" => Some(Rating::Questionable),
+            "e" => Some(Rating::Explicit),
+            _ => None,
+        });
+
+        Ok(Self {
+            rating,
+            score: value.score,
+            width: value.width,
+            height: value.height,
+            source: value.source.filter(|source| !source.is_empty()),
+            created_at: value.created_at.map(|created_at| created_at.to_string()),
+            owner: value.author,
+            parent_id: value.parent_id,
+            has_children: value.has_children,
+            sample_url: value.sample_url,
+            preview_url: value.preview_url,
+            file_size: value.file_size,
+            status: value.status,
+            ..Self::new(value.id, value.md5, file_url, value.tags, image)
+        })
+    }
+}
+
+/// A Consuming-Builders style function to get the posts from the Moebooru API.
+///
+/// # Example
+///
+/// ```rust
+/// use reqwest::Client;
+/// use booru_dl::api::moebooru::Getter;
+///
+/// #[tokio::main]
+/// async fn main() -> reqwest::Result<()> {
+///     let client = Client::new();
+///     let tags = "cat";
+///     let limit = 10;
+///     let page = 1;
+///
+///     let posts = Getter::build(&client, &tags, limit, page)
+///         .expect("illegal arguments")
+///         .run()
+///         .await?;
+///
+///     Ok(())
+/// }
+/// ```
+pub struct Getter<'a> {
+    client: &'a Client,
+    base_url: &'a Url,
+    options: &'a RequestOptions,
+    tags: &'a str,
+    limit: u64,
+    page: u64,
+}
+
+impl Getter<'_> {
+    /// See <https://yande.re/help/api> for arguments.
+    ///
+    /// Note: `page` is one-based.
+    ///
+    /// # Errors
+    ///
+    /// If `tags` is empty, or `limit` is not in the range `1..=100`,
+    /// or `page` is 0, this function will return an error.
+    pub fn build<'a>(
+        client: &'a Client,
+        tags: &'a str,
+        limit: u64,
+        page: u64,
+    ) -> anyhow::Result<Getter<'a>> {
+        if tags.is_empty() {
+            return Err(anyhow::anyhow!("Tags cannot be empty"));
+        }
+        if !matches!(limit, 1..=Moebooru::MAX_LIMIT) {
+            return Err(anyhow::anyhow!("Limit can only be between 1 and 100"));
+        }
+        if page == 0 {
+            return Err(anyhow::anyhow!("Page starts from 1"));
+        }
+        Ok(Getter {
+            client,
+            base_url: &url::YANDERE_BASE,
+            options: &RequestOptions::DEFAULT,
+            tags,
+            limit,
+            page,
+        })
+    }
+
+    /// Send the request to the Moebooru API and get the posts.
+    ///
+    /// Note: Moebooru doesn't return the total number of posts,
+    /// an empty vector means there are no more posts.
+    ///
+    /// # Errors
+    ///
+    /// If the request still fails after retrying with [`Self::options`],
+    /// or the server returns an error status, this function will return an error.
+    pub async fn run(self) -> reqwest::Result<Vec<data::field::Post>> {
+        let mut target_url = self.base_url.join(url::POSTS_PATH).unwrap();
+        target_url.query_pairs_mut().extend_pairs([
+            ("tags", self.tags),
+            ("limit", &self.limit.to_string()),
+            ("page", &self.page.to_string()),
+        ]);
+        self.options.send_json(self.client.get(target_url)).await
+    }
+}
+
+impl<'a> Getter<'a> {
+    /// Use another Moebooru instance instead of [`url::YANDERE_BASE_URL`].
+    ///
+    /// The `base_url` should end with `/`, or its last path segment will be replaced.
+    pub fn base_url(mut self, base_url: &'a Url) -> Self {
+        self.base_url = base_url;
+        self
+    }
+
+    /// Send the requests with `options`, default to [`RequestOptions::DEFAULT`].
+    pub fn options(mut self, options: &'a RequestOptions) -> Self {
+        self.options = options;
+        self
+    }
+}
+
+/// The [`Booru`] implementation for Moebooru, which wraps [`Getter`].
+///
+/// `page` of [`Booru::search`] is zero-based, but Moebooru's `page` is one-based.
+/// Deleted posts without `file_url` are skipped.
+///
+/// Moebooru doesn't report the total number of posts,
+/// so [`crate::api::BatchGetter`] will keep paging until an empty page.
+#[non_exhaustive]
+#[derive(Debug, Clone)]
+pub struct Moebooru {
+    /// The base URL of the Moebooru instance, see [`Getter::base_url`].
+    pub base_url: Url,
+    /// The options of the API requests, see [`Getter::options`].
+    pub options: RequestOptions,
+}
+
+impl Moebooru {
+    /// The maximum number of posts in a single page.
+    pub const MAX_LIMIT: u64 = 100;
+
+    /// Create a backend for the Moebooru instance at `base_url`.
+    pub fn new(base_url: Url) -> Self {
+        Self {
+            base_url,
+            options: RequestOptions::DEFAULT,
+        }
+    }
+
+    /// Send the API requests with `options`, see [`Getter::options`].
+    pub fn with_options(mut self, options: RequestOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Create a backend for [yande.re](https://yande.re/).
+    pub fn yandere() -> Self {
+        Self::new(url::YANDERE_BASE.clone())
+    }
+
+    /// Create a backend for [Konachan](https://konachan.com/).
+    pub fn konachan() -> Self {
+        Self::new(url::KONACHAN_BASE.clone())
+    }
+}
+
+impl Booru for Moebooru {
+    #[inline]
+    fn max_limit(&self) -> u64 {
+        Self::MAX_LIMIT
+    }
+
+    fn search<'a>(
+        &'a self,
+        client: &'a Client,
+        tags: &'a str,
+        limit: u64,
+        page: u64,
+    ) -> impl Future<Output = reqwest::Result<Page>> + Send + 'a {
+        let getter = Getter::build(client, tags, limit, page + 1)
+            .expect("illegal search arguments")
+            .base_url(&self.base_url)
+            .options(&self.options);
+        async move {
+            let raw_posts = getter.run().await?;
+            let exhausted = raw_posts.is_empty();
+            let posts = raw_posts
+                .into_iter()
+                .filter_map(|post| post.try_into().ok())
+                .collect();
+            Ok(Page::new(posts, None).with_exhausted(exhausted))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use crate::api::BatchGetter;
+
+    /// A synthetic first page shaped like the yande.re `post.json`, not a recorded response.
+    ///
+    /// The second post omits `file_ext` and the metadata, like the old Moebooru versions.
+    const PAGE_1_JSON: &str = r#"[
+        {
+            "id": 1186710,
+            "tags": "animal_ears cat nekomimi",
+            "md5": "8d2e6f4b1c0a9e7d3f5b2a1c4e6d8f0a",
+            "file_url": "https://files.yande.re/image/8d2e6f4b1c0a9e7d3f5b2a1c4e6d8f0a/yande.re%201186710%20animal_ears.png",
+            "jpeg_url": "https://files.yande.re/jpeg/8d2e6f4b1c0a9e7d3f5b2a1c4e6d8f0a/yande.re%201186710%20animal_ears.jpg",
+            "sample_url": "https://files.yande.re/sample/8d2e6f4b1c0a9e7d3f5b2a1c4e6d8f0a/yande.re%201186710%20sample.jpg",
+            "file_ext": "png",
+            "rating": "s",
+            "score": 7,
+            "width": 4000,
+            "height": 3000,
+            "source": "https://www.pixiv.net/artworks/12345678",
+            "created_at": 1723312302,
+            "author": "moe",
+            "parent_id": 1186700,
+            "has_children": false,
+            "file_size": 2097152,
+            "status": "active"
+        },
+        {
+            "id": 1186702,
+            "tags": "cat",
+            "md5": "0a1b2c3d4e5f60718293a4b5c6d7e8f9",
+            "file_url": "https://files.yande.re/image/0a1b2c3d4e5f60718293a4b5c6d7e8f9/yande.re%201186702%20cat.jpg"
+        }
+    ]"#;
+
+    /// A synthetic second page, which only has a deleted post.
+    const PAGE_2_JSON: &str = r#"[
+        {
+            "id": 1186699,
+            "tags": "cat",
+            "md5": "f9e8d7c6b5a4938271605f4e3d2c1b0a",
+            "file_url": null
+        }
+    ]"#;
+
+    async fn mock_server() -> MockServer {
+        let server = MockServer::start().await;
+        for (page, body) in [("1", PAGE_1_JSON), ("2", PAGE_2_JSON), ("3", "[]")] {
+            Mock::given(method("GET"))
+                .and(path("/post.json"))
+                .and(query_param("page", page))
+                .respond_with(ResponseTemplate::new(200).set_body_raw(body, "application/json"))
+                .mount(&server)
+                .await;
+        }
+        server
+    }
+
+    #[tokio::test]
+    async fn test_search_fake_server() -> reqwest::Result<()> {
+        let server = mock_server().await;
+        let client = Client::new();
+        let booru = Moebooru::new(server.uri().parse().unwrap());
+
+        let page = booru.search(&client, "cat", 2, 0).await?;
+        assert_eq!(page.posts.len(), 2);
+        let post = &page.posts[0];
+        assert_eq!(post.filename, PathBuf::from("1186710.png"));
+        assert_eq!(post.rating, Some(Rating::General));
+        assert_eq!(post.score, Some(7));
+        assert_eq!((post.width, post.height), (Some(4000), Some(3000)));
+        assert_eq!(post.created_at.as_deref(), Some("1723312302"));
+        assert_eq!(post.owner.as_deref(), Some("moe"));
+        assert_eq!(post.parent_id, Some(1186700));
+        assert_eq!(post.status.as_deref(), Some("active"));
+        // `file_ext` is absent, fallback to the extension of `file_url`
+        assert_eq!(page.posts[1].filename, PathBuf::from("1186702.jpg"));
+
+        // the deleted post is skipped, but the page is not exhausted
+        let page = booru.search(&client, "cat", 2, 1).await?;
+        assert!(page.posts.is_empty());
+        assert!(!page.exhausted);
+
+        // there is no count, so keep paging until the empty page
+        let posts = BatchGetter::build(&client, &booru, "cat", 10)
+            .unwrap()
+            .run()
+            .await?;
+        assert_eq!(posts.len(), 2);
+        Ok(())
+    }
+}
@@ -14,10 +14,39 @@ use crate::config::{Config, Validate, DEFAULT_CONFIG_STR};
 
 const EDITOR_EXTENSION: &str = ".toml";
 
+/// The exit codes of the command line program, which are documented in `--help`.
+pub mod exit_code {
+    /// Some error not covered by the other codes, e.g. failed to create the download directory.
+    pub const ERROR: u8 = 1;
+    /// The config is invalid, or failed to be read. This is the same as the [`clap`] usage error.
+    pub const CONFIG_ERROR: u8 = 2;
+    /// Some images failed to be downloaded.
+    pub const SOME_FAILED: u8 = 3;
+    /// All images failed to be downloaded.
+    pub const ALL_FAILED: u8 = 4;
+    /// Failed to get the post data from the API.
+    pub const API_ERROR: u8 = 5;
+    /// Interrupted by Ctrl-C, which is the same as the shell convention `128 + SIGINT`.
+    pub const INTERRUPTED: u8 = 130;
+}
+
+const EXIT_CODES_HELP: &str = "Exit codes:
+    0  All images were downloaded, or already existed
+    1  Unexpected error, e.g. failed to create the download directory
+    2  Invalid config
+    3  Some images failed to be downloaded
+    4  All images failed to be downloaded
+    5  Failed to get the post data from the API
+  130  Interrupted by Ctrl-C
+
+On the first Ctrl-C, the in-flight downloads are given a grace period to complete,
+press Ctrl-C again to exit immediately.";
+
 /// [`clap`] command line interface.
 ///
 /// The [`Self::parse`] trait and [`Self::get_config_from_editor`]
 /// will use [`toml`] to parse the config file,
+/// fill the missing credentials with [`Config::with_env`],
 /// then use [`Config::validate`] to validate the config.
 ///
 /// You need check out the [`Self`] source code to figure out what [`Self`] do when parsing the config.
@@ -32,7 +61,7 @@ const EDITOR_EXTENSION: &str = ".toml";
 #[non_exhaustive]
 #[derive(Parser)]
 // NOTE: `long_about=None` is important, or the docstring will be treated as the long about.
-#[command(version, about, long_about=None)]
+#[command(version, about, long_about=None, after_help=EXIT_CODES_HELP)]
 pub struct Cli {
     /// The config file to use.
     ///
@@ -54,7 +83,7 @@ impl Cli {
     #[inline]
     fn parse_config_from_filepath(path: PathBuf) -> anyhow::Result<Config> {
         let config = std::fs::read_to_string(path)?;
-        let config = toml::from_str::<Config>(&config)?;
+        let config = toml::from_str::<Config>(&config)?.with_env();
         config.validate()?;
         Ok(config)
     }
@@ -95,7 +124,7 @@ impl Cli {
             }
         };
         let config = match toml::from_str::<Config>(&config) {
-            Ok(config) => config,
+            Ok(config) => config.with_env(),
             Err(err) => return Err(cmd.error(ErrorKind::ValueValidation, err)),
         };
 
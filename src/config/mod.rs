@@ -9,38 +9,243 @@
 // we only need these for documentation, or the link will be too long.
 use crate::cli::{Cli, Parser};
 
-use std::num::NonZeroU64;
+use std::num::{NonZeroU32, NonZeroU64, NonZeroUsize};
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
 use serde::Deserialize;
 pub use validator::Validate;
+use validator::ValidationError;
+
+use crate::api::gelbooru::Credentials;
+use crate::api::{AnyBooru, BatchGetter, Site};
+use crate::filter::{Blacklist, PostFilter};
+use crate::rate_limit::RateLimiter;
+use crate::retry::RetryPolicy;
+use crate::scheduler::Scheduler;
+use crate::tool::Secret;
 
 /// The default config string.
 pub const DEFAULT_CONFIG_STR: &str = include_str!("default.toml");
 
+/// The environment variable to read [`Config::user_id`] from.
+pub const USER_ID_ENV: &str = "BOORU_DL_USER_ID";
+
+/// The environment variable to read [`Config::api_key`] from.
+pub const API_KEY_ENV: &str = "BOORU_DL_API_KEY";
+
 /// The config data struct.
 ///
 /// This struct impl [`Deserialize`] and [`Validate`] to parse and validate the config.
 #[non_exhaustive]
 #[derive(Debug, Deserialize, Clone, Validate)]
+#[validate(schema(function = "validate_credentials"))]
 pub struct Config {
+    /// The booru site to download from.
+    ///
+    /// Default to [`Site::Gelbooru`] if not specified.
+    #[serde(default)]
+    pub site: Site,
+    /// The base URL of the booru site.
+    ///
+    /// If `None`, use the default URL of [`Self::site`].
+    /// Set this to use a compatible site or a self-hosted mirror,
+    /// e.g. `https://safebooru.org/index.php` for [`Site::Gelbooru`].
+    ///
+    /// This field is validated to ensure it is a valid URL.
+    #[validate(url(message = "base_url must be a valid URL"))]
+    pub base_url: Option<String>,
+    /// The user ID for authenticated requests, only used by [`Site::Gelbooru`].
+    ///
+    /// If `None`, [`Self::with_env`] will read it from [`USER_ID_ENV`].
+    ///
+    /// This field is validated to ensure it is set together with [`Self::api_key`].
+    pub user_id: Option<String>,
+    /// The API key for authenticated requests, only used by [`Site::Gelbooru`].
+    ///
+    /// If `None`, [`Self::with_env`] will read it from [`API_KEY_ENV`],
+    /// so that you don't have to write it into the config file.
+    pub api_key: Option<Secret>,
     /// The tags to search for.
     ///
     /// This field is validated to ensure it is not empty.
     #[validate(length(min = 1, message = "tags must not be empty"))]
     pub tags: String,
-    /// The number of images to download.
+    /// The blacklist of tags, which supports `*` wildcards, e.g. `["comic", "*_censor*"]`.
+    ///
+    /// This is checked client-side, so it isn't limited by the number of tags in a query.
+    /// Default to empty if not specified, see [`Blacklist`].
+    #[serde(default)]
+    pub blacklist: Blacklist,
+    /// The number of images to download,
+    /// which counts only the posts passing [`Self::filter`] and [`Self::blacklist`].
     pub num_imgs: NonZeroU64,
+    /// The client-side filter of the posts, i.e. the `[filter]` table.
+    ///
+    /// Default to no filter if not specified, see [`BatchGetter::filter`].
+    #[serde(default)]
+    #[validate(nested)]
+    pub filter: PostFilter,
     /// The directory to download the images to.
     pub download_dir: PathBuf,
     /// The timeout for the request.
     pub timeout: u64,
+    /// The maximum number of API pages to fetch concurrently.
+    ///
+    /// Default to [`BatchGetter::DEFAULT_CONCURRENCY`] if not specified.
+    #[serde(default = "default_api_concurrency")]
+    pub api_concurrency: NonZeroUsize,
+    /// The maximum number of attempts for an API request, including the first one.
+    ///
+    /// Default to [`RetryPolicy::DEFAULT`] if not specified.
+    #[serde(default = "default_max_attempts")]
+    pub api_max_attempts: NonZeroU32,
+    /// The delay in milliseconds before the first retry of an API request,
+    /// which is doubled on each retry.
+    ///
+    /// Default to [`RetryPolicy::DEFAULT`] if not specified.
+    #[serde(default = "default_api_retry_delay")]
+    pub api_retry_delay: u64,
+    /// The maximum number of API requests per second.
+    ///
+    /// If `None`, the request rate is not limited, see [`AnyBooru::with_request_rate`].
+    pub api_requests_per_sec: Option<NonZeroU64>,
+    /// The maximum number of attempts for an image download, including the first one.
+    ///
+    /// Default to [`RetryPolicy::DEFAULT`] if not specified.
+    #[serde(default = "default_max_attempts")]
+    pub download_max_attempts: NonZeroU32,
+    /// Whether to sync the downloaded files to disk, see [`crate::scheduler::Scheduler::fsync`].
+    ///
+    /// Default to `false` if not specified.
+    #[serde(default)]
+    pub fsync: bool,
+    /// The maximum number of concurrent downloads.
+    ///
+    /// Default to [`Scheduler::DEFAULT_DOWNLOAD_CONCURRENCY`] if not specified.
+    #[serde(default = "default_download_concurrency")]
+    pub download_concurrency: NonZeroUsize,
+    /// The maximum number of concurrent existence checks, which hash the existing files.
+    ///
+    /// If `None`, use the number of CPUs, see [`Scheduler::hash_concurrency`].
+    pub hash_concurrency: Option<NonZeroUsize>,
+    /// The maximum total download bandwidth in bytes per second.
+    ///
+    /// If `None`, the bandwidth is not limited, see [`Scheduler::max_bytes_per_sec`].
+    pub max_bytes_per_sec: Option<NonZeroU64>,
+    /// The maximum number of requests per second to the image hosts.
+    ///
+    /// If `None`, the request rate is not limited, see [`Scheduler::max_requests_per_sec`].
+    pub download_requests_per_sec: Option<NonZeroU64>,
+}
+
+fn default_api_concurrency() -> NonZeroUsize {
+    BatchGetter::<AnyBooru>::DEFAULT_CONCURRENCY
+}
+
+fn default_download_concurrency() -> NonZeroUsize {
+    Scheduler::DEFAULT_DOWNLOAD_CONCURRENCY
+}
+
+fn default_max_attempts() -> NonZeroU32 {
+    RetryPolicy::DEFAULT.max_attempts
+}
+
+fn default_api_retry_delay() -> u64 {
+    RetryPolicy::DEFAULT
+        .base_delay
+        .as_millis()
+        .try_into()
+        .unwrap()
+}
+
+fn validate_credentials(config: &Config) -> Result<(), ValidationError> {
+    if config.user_id.is_some() != config.api_key.is_some() {
+        return Err(ValidationError::new("credentials")
+            .with_message("user_id and api_key must be set together".into()));
+    }
+    Ok(())
+}
+
+impl Config {
+    /// Fill the missing [`Self::user_id`] and [`Self::api_key`] from the environment variables.
+    ///
+    /// The empty environment variables are ignored.
+    ///
+    /// Call this before [`Validate::validate`].
+    pub fn with_env(self) -> Self {
+        self.with_vars(|key| std::env::var(key).ok())
+    }
+
+    /// [`Self::with_env`] reading the variables from `var`, so that the tests don't touch the environment.
+    fn with_vars(mut self, var: impl Fn(&str) -> Option<String>) -> Self {
+        let var = |key| var(key).filter(|value| !value.is_empty());
+        if self.user_id.is_none() {
+            self.user_id = var(USER_ID_ENV);
+        }
+        if self.api_key.is_none() {
+            self.api_key = var(API_KEY_ENV).map(Secret::new);
+        }
+        self
+    }
+
+    /// Get the [`Credentials`] if both [`Self::user_id`] and [`Self::api_key`] are set.
+    pub fn credentials(&self) -> Option<Credentials> {
+        match (&self.user_id, &self.api_key) {
+            (Some(user_id), Some(api_key)) => Some(Credentials::new(user_id, api_key.clone())),
+            _ => None,
+        }
+    }
+
+    /// Get the [`RetryPolicy`] from [`Self::api_max_attempts`] and [`Self::api_retry_delay`].
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy::new(
+            self.api_max_attempts,
+            Duration::from_millis(self.api_retry_delay),
+        )
+    }
+
+    /// Get the [`RetryPolicy`] for image downloads from [`Self::download_max_attempts`].
+    pub fn download_retry_policy(&self) -> RetryPolicy {
+        RetryPolicy::new(self.download_max_attempts, RetryPolicy::DEFAULT.base_delay)
+    }
+
+    /// Create the [`AnyBooru`] backend from [`Self::site`], [`Self::base_url`],
+    /// [`Self::credentials`], [`Self::retry_policy`] and [`Self::api_requests_per_sec`].
+    ///
+    /// # Panics
+    ///
+    /// If [`Self::base_url`] is not a valid URL, which should be checked by [`Validate::validate`].
+    pub fn booru(&self) -> AnyBooru {
+        let booru = match &self.base_url {
+            Some(base_url) => self
+                .site
+                .with_base_url(base_url.parse().expect("invalid base_url")),
+            None => self.site.into(),
+        };
+        let booru = match (booru, self.credentials()) {
+            (AnyBooru::Gelbooru(booru), Some(credentials)) => {
+                booru.with_credentials(credentials).into()
+            }
+            (booru, _) => booru,
+        };
+        let booru = booru.with_retry(self.retry_policy());
+        match self.api_requests_per_sec {
+            Some(api_requests_per_sec) => {
+                booru.with_request_rate(Arc::new(RateLimiter::new(api_requests_per_sec)))
+            }
+            None => booru,
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use crate::api::data::field::Rating;
+
     #[test]
     fn test_parse_default_config() -> anyhow::Result<()> {
         let config: Config = toml::from_str(DEFAULT_CONFIG_STR)?;
@@ -59,4 +264,202 @@ mod tests {
         let config: Config = toml::from_str(toml).unwrap();
         config.validate().expect_err("empty tags should be invalid");
     }
+
+    #[test]
+    fn test_parse_site() {
+        let toml = r#"
+            site = "gelbooru"
+            tags = "cat"
+            num_imgs = 1
+            download_dir = "test"
+            timeout = 10
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(config.site, Site::Gelbooru);
+        assert_eq!(
+            config.api_concurrency,
+            BatchGetter::<AnyBooru>::DEFAULT_CONCURRENCY
+        );
+        assert_eq!(config.retry_policy(), RetryPolicy::DEFAULT);
+        assert_eq!(config.download_retry_policy(), RetryPolicy::DEFAULT);
+        assert_eq!(
+            config.download_concurrency,
+            Scheduler::DEFAULT_DOWNLOAD_CONCURRENCY
+        );
+        assert_eq!(config.hash_concurrency, None);
+        assert!(config.filter.is_empty());
+        assert!(config.blacklist.is_empty());
+
+        let toml = r#"
+            site = "balabala"
+            tags = "cat"
+            num_imgs = 1
+            download_dir = "test"
+            timeout = 10
+        "#;
+        toml::from_str::<Config>(toml).expect_err("unknown site should be invalid");
+    }
+
+    #[test]
+    fn test_parse_base_url() {
+        let toml = r#"
+            base_url = "https://safebooru.org/index.php"
+            tags = "cat"
+            num_imgs = 1
+            download_dir = "test"
+            timeout = 10
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+        config.validate().unwrap();
+        assert!(
+            matches!(config.booru(), AnyBooru::Gelbooru(booru) if booru.base_url.as_str() == "https://safebooru.org/index.php")
+        );
+
+        let toml = r#"
+            base_url = "not a url"
+            tags = "cat"
+            num_imgs = 1
+            download_dir = "test"
+            timeout = 10
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+        config
+            .validate()
+            .expect_err("invalid base_url should be invalid");
+    }
+
+    #[test]
+    fn test_with_env() {
+        let toml = r#"
+            user_id = "42"
+            tags = "cat"
+            num_imgs = 1
+            download_dir = "test"
+            timeout = 10
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+        let config = config.with_vars(|key| match key {
+            USER_ID_ENV => Some(String::from("24")),
+            API_KEY_ENV => Some(String::from("my_secret_api_key")),
+            _ => None,
+        });
+        // the config value wins over the environment variable
+        assert_eq!(config.user_id.as_deref(), Some("42"));
+        // the environment variable fills the missing value
+        assert_eq!(
+            config.api_key.as_ref().map(Secret::expose),
+            Some("my_secret_api_key")
+        );
+
+        let config: Config = toml::from_str(toml).unwrap();
+        let config = config.with_vars(|_| Some(String::new()));
+        // the empty environment variable is ignored
+        assert_eq!(config.api_key, None);
+        config
+            .validate()
+            .expect_err("user_id without api_key should be invalid");
+    }
+
+    #[test]
+    fn test_parse_filter() {
+        let toml = r#"
+            tags = "cat"
+            num_imgs = 1
+            download_dir = "test"
+            timeout = 10
+
+            [filter]
+            ratings = ["general", "sensitive"]
+            min_score = 10
+            min_width = 1920
+            min_aspect_ratio = 1.5
+            extensions = ["jpg", "png"]
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.filter.ratings, [Rating::General, Rating::Sensitive]);
+        assert_eq!(config.filter.min_score, Some(10));
+        assert_eq!(config.filter.min_width, Some(1920));
+        assert_eq!(config.filter.max_width, None);
+        assert_eq!(config.filter.min_aspect_ratio, Some(1.5));
+        assert_eq!(config.filter.extensions, ["jpg", "png"]);
+
+        let toml = r#"
+            tags = "cat"
+            num_imgs = 1
+            download_dir = "test"
+            timeout = 10
+
+            [filter]
+            min_height = 1080
+            max_height = 720
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+        config
+            .validate()
+            .expect_err("min_height > max_height should be invalid");
+
+        let toml = r#"
+            tags = "cat"
+            num_imgs = 1
+            download_dir = "test"
+            timeout = 10
+
+            [filter]
+            ratings = ["safe"]
+        "#;
+        toml::from_str::<Config>(toml).expect_err("unknown rating should be invalid");
+    }
+
+    #[test]
+    fn test_parse_blacklist() {
+        let toml = r#"
+            tags = "cat"
+            blacklist = ["comic", "*_censor*"]
+            num_imgs = 1
+            download_dir = "test"
+            timeout = 10
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+        assert!(config.blacklist.matches("comic"));
+        assert!(config.blacklist.matches("bar_censor"));
+        assert!(!config.blacklist.matches("cat"));
+
+        let toml = r#"
+            tags = "cat"
+            blacklist = [""]
+            num_imgs = 1
+            download_dir = "test"
+            timeout = 10
+        "#;
+        toml::from_str::<Config>(toml).expect_err("empty pattern should be invalid");
+    }
+
+    #[test]
+    fn test_parse_credentials() {
+        let toml = r#"
+            user_id = "42"
+            api_key = "my_secret_api_key"
+            tags = "cat"
+            num_imgs = 1
+            download_dir = "test"
+            timeout = 10
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+        config.validate().unwrap();
+        assert!(!format!("{config:?}").contains("my_secret_api_key"));
+        assert!(matches!(config.booru(), AnyBooru::Gelbooru(booru) if booru.credentials.is_some()));
+
+        let toml = r#"
+            user_id = "42"
+            tags = "cat"
+            num_imgs = 1
+            download_dir = "test"
+            timeout = 10
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+        config
+            .validate()
+            .expect_err("user_id without api_key should be invalid");
+    }
 }
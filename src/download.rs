@@ -5,14 +5,21 @@
 //! Usually, you prefer to use high-level [`crate::scheduler`] to download images from api data.
 
 use std::future::Future;
+use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Weak;
+use std::sync::{Arc, Weak};
 
-use reqwest::{Client, IntoUrl};
+use digest::{Digest, DynDigest};
+use reqwest::header::{CONTENT_RANGE, RANGE};
+use reqwest::{Client, IntoUrl, Response, StatusCode};
 use thiserror::Error;
-use tokio::fs::{create_dir_all, File};
-use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::fs::{create_dir_all, File, OpenOptions};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, BufWriter};
+use tokio::sync::watch;
+
+use crate::rate_limit::RateLimiter;
+use crate::retry::RetryPolicy;
 
 /// The error type for downloading.
 #[non_exhaustive]
@@ -27,16 +34,125 @@ pub enum DownloadError {
     /// The server returned zero content length. This not your fault.
     #[error("There is no content to download")]
     ZeroContentLength,
+    /// The digest of the downloaded file doesn't match the expected one,
+    /// see [`DownloadFutureBuilder::verify`].
+    #[error("Checksum mismatch, expected: {expected}, actual: {actual}")]
+    ChecksumMismatch {
+        /// The expected digest in lower case hex.
+        expected: String,
+        /// The actual digest in lower case hex.
+        actual: String,
+    },
     /// Failed to allocate file on disk.
+    #[deprecated(
+        note = "the file is no longer preallocated, so that the part file can be resumed"
+    )]
     #[error("Failed to allocate file size: {0}")]
     FileAllocationFailed(std::io::Error),
 }
 
+impl DownloadError {
+    /// Whether the download is worth retrying, e.g. timeout, `5xx` or interrupted I/O.
+    ///
+    /// See [`RetryPolicy::is_retryable_error`] and [`RetryPolicy::is_retryable_status`]
+    /// for [`Self::Reqwest`].
+    pub fn is_retryable(&self) -> bool {
+        #[allow(deprecated)]
+        match self {
+            Self::Io(err) => matches!(
+                err.kind(),
+                ErrorKind::Interrupted | ErrorKind::TimedOut | ErrorKind::ConnectionReset
+            ),
+            Self::Reqwest(err) => {
+                RetryPolicy::is_retryable_error(err)
+                    || err.status().is_some_and(RetryPolicy::is_retryable_status)
+            }
+            Self::ZeroContentLength
+            | Self::ChecksumMismatch { .. }
+            | Self::FileAllocationFailed(_) => false,
+        }
+    }
+}
+
+/// The extension appended to the file path while downloading, e.g. `image.jpg.part`.
+pub const PART_EXTENSION: &str = "part";
+
+/// Get the path of the partially downloaded file, see [`PART_EXTENSION`].
+fn part_path(file_path: &Path) -> PathBuf {
+    let mut part_path = file_path.as_os_str().to_owned();
+    part_path.push(".");
+    part_path.push(PART_EXTENSION);
+    part_path.into()
+}
+
+/// Write `contents` to `path` atomically.
+///
+/// The `contents` is written to a [`PART_EXTENSION`] sibling first, then renamed to `path`,
+/// so `path` is either absent or complete, even if the program crashes.
+/// If `fsync` is `true`, the file is synced to disk before renaming.
+///
+/// # Errors
+///
+/// If the file can't be written or renamed, an error will be returned.
+pub async fn write_atomic(
+    path: impl AsRef<Path>,
+    contents: impl AsRef<[u8]>,
+    fsync: bool,
+) -> std::io::Result<()> {
+    let path = path.as_ref();
+    let part_path = part_path(path);
+    let mut file = File::create(&part_path).await?;
+    file.write_all(contents.as_ref()).await?;
+    if fsync {
+        file.sync_all().await?;
+    }
+    // close the file before renaming, which is required on Windows
+    drop(file);
+    tokio::fs::rename(&part_path, path).await
+}
+
+/// Get the first byte position of the `Content-Range: bytes {start}-{end}/{len}` header.
+fn content_range_start(response: &Response) -> Option<u64> {
+    let content_range = response.headers().get(CONTENT_RANGE)?.to_str().ok()?;
+    let (start, _) = content_range.strip_prefix("bytes ")?.split_once('-')?;
+    start.trim().parse().ok()
+}
+
+/// The expected digest of the file, see [`DownloadFutureBuilder::verify`].
+#[derive(Clone)]
+struct Checksum {
+    new_hasher: fn() -> Box<dyn DynDigest + Send>,
+    expected: String,
+}
+
+/// Update `hasher` with the remaining content of `file`.
+async fn update_hasher(
+    file: &mut File,
+    hasher: &mut (dyn DynDigest + Send),
+) -> std::io::Result<()> {
+    const BUF_SIZE: usize = 64 * 1024; // 64KB
+
+    let mut buf = vec![0; BUF_SIZE];
+    loop {
+        let n = file.read(&mut buf).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        hasher.update(&buf[..n]);
+    }
+}
+
+/// The callback of [`DownloadFutureBuilder::on_data`].
+type DataCallback = Arc<dyn Fn(u64) + Send + Sync>;
+/// The callback of [`DownloadFutureBuilder::on_response`].
+type ResponseCallback = Arc<dyn Fn(Option<u64>) + Send + Sync>;
+
 /// A Consuming-Builders to create a download future. This struct is crated by [`Downloader::future`].
 ///
 /// # Example
 ///
 /// See [`Downloader#example`].
+#[derive(Clone)]
 pub struct DownloadFutureBuilder<U, P>
 where
     U: IntoUrl,
@@ -45,7 +161,14 @@ where
     client: Client,
     url: U,
     file_path: P,
-    data_cursor: Option<Weak<AtomicUsize>>,
+    data_cursors: Vec<Weak<AtomicUsize>>,
+    on_data: Option<DataCallback>,
+    on_response: Option<ResponseCallback>,
+    fsync: bool,
+    checksum: Option<Checksum>,
+    bandwidth: Option<Arc<RateLimiter>>,
+    request_rate: Option<Arc<RateLimiter>>,
+    pause: Option<watch::Receiver<bool>>,
 }
 
 impl<U, P> DownloadFutureBuilder<U, P>
@@ -58,7 +181,14 @@ where
             client,
             url,
             file_path,
-            data_cursor: None,
+            data_cursors: Vec::new(),
+            on_data: None,
+            on_response: None,
+            fsync: false,
+            checksum: None,
+            bandwidth: None,
+            request_rate: None,
+            pause: None,
         }
     }
 
@@ -67,55 +197,260 @@ where
     /// Every time a chunk is written to the file,
     /// the data cursor will be updated through [`fetch_add`](std::sync::atomic::AtomicUsize::fetch_add)
     /// with [`Ordering::Release`], if the data cursor is still alive.
-    pub fn add_data_cursor(mut self, speed_cursor: Weak<AtomicUsize>) -> Self {
-        self.data_cursor = Some(speed_cursor);
+    ///
+    /// Call this multiple times to add multiple data cursors,
+    /// e.g. one for the total speed and one for this download.
+    pub fn add_data_cursor(mut self, data_cursor: Weak<AtomicUsize>) -> Self {
+        self.data_cursors.push(data_cursor);
+        self
+    }
+
+    /// Call `on_data` with the chunk length every time a chunk is written to the file,
+    /// after the data cursors are updated, see [`Self::add_data_cursor`].
+    pub fn on_data(mut self, on_data: impl Fn(u64) + Send + Sync + 'static) -> Self {
+        self.on_data = Some(Arc::new(on_data));
+        self
+    }
+
+    /// Call `on_response` with the content length of the response before writing the chunks,
+    /// i.e. the remaining length if the download is resumed, or `None` if unknown.
+    pub fn on_response(
+        mut self,
+        on_response: impl Fn(Option<u64>) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_response = Some(Arc::new(on_response));
+        self
+    }
+
+    /// Sync the file to disk before renaming it to `file_path`, default to `false`.
+    ///
+    /// This makes sure the file survives a power failure, at the cost of performance.
+    pub fn fsync(mut self, fsync: bool) -> Self {
+        self.fsync = fsync;
+        self
+    }
+
+    /// Limit the download bandwidth with `bandwidth` in bytes per second.
+    ///
+    /// Every chunk acquires its length from `bandwidth` before being written,
+    /// so share the same `bandwidth` among the downloads to limit the total bandwidth.
+    pub fn bandwidth(mut self, bandwidth: Arc<RateLimiter>) -> Self {
+        self.bandwidth = Some(bandwidth);
+        self
+    }
+
+    /// Limit the request rate with `request_rate` in requests per second.
+    ///
+    /// Every request (including the resuming one) acquires a token from `request_rate` before being sent,
+    /// so share the same `request_rate` among the downloads to limit the total request rate.
+    pub fn request_rate(mut self, request_rate: Arc<RateLimiter>) -> Self {
+        self.request_rate = Some(request_rate);
+        self
+    }
+
+    /// Pause the download while the value of `pause` is `true`.
+    ///
+    /// No request is sent and no chunk is pulled while paused,
+    /// so the connection may time out, then the download can be retried and resumed.
+    pub fn pause(mut self, pause: watch::Receiver<bool>) -> Self {
+        self.pause = Some(pause);
+        self
+    }
+
+    /// Verify the downloaded file with the `expected` hex digest of `D`, e.g. [`md5::Md5`].
+    ///
+    /// The chunks are hashed as they are written, so the file is not read again.
+    /// If the digest mismatches, the file will be deleted and
+    /// [`DownloadError::ChecksumMismatch`] will be returned.
+    ///
+    /// If the download was resumed from a part file, which may be stale or corrupt,
+    /// it is downloaded again from scratch once before returning the error.
+    pub fn verify<D>(mut self, expected: impl Into<String>) -> Self
+    where
+        D: Digest + DynDigest + Send + 'static,
+    {
+        self.checksum = Some(Checksum {
+            new_hasher: || Box::new(<D as Digest>::new()),
+            expected: expected.into(),
+        });
         self
     }
 
     /// Transform this builder into a future.
+    ///
+    /// The file is written to a [`PART_EXTENSION`] file first,
+    /// and renamed to `file_path` after the download is completed,
+    /// so `file_path` is either absent or complete, even if the program crashes.
+    ///
+    /// If the part file already exists, e.g. the previous download was interrupted,
+    /// the download will be resumed with a `Range: bytes={part_file_len}-` request.
+    /// If the server responds `206 Partial Content` with a `Content-Range` starting at `part_file_len`,
+    /// the content is appended to the part file. Otherwise the file is downloaded from scratch, i.e.
+    /// - `200 OK`, e.g. the server doesn't support range requests, replaces the part file;
+    /// - `416 Range Not Satisfiable` or a `Content-Range` starting elsewhere is discarded and
+    ///   the file is requested again without the range.
     pub fn build(self) -> impl Future<Output = Result<P, DownloadError>> {
         let Self {
             client,
             url,
             file_path,
-            data_cursor,
+            data_cursors,
+            on_data,
+            on_response,
+            fsync,
+            checksum,
+            bandwidth,
+            request_rate,
+            pause,
         } = self;
 
         async move {
-            let mut response = client.get(url).send().await?.error_for_status()?;
-            let mut file_buf = BufWriter::new(File::create(&file_path).await?);
+            let url = url.into_url()?;
+            let part_path = part_path(file_path.as_ref());
 
-            // pre-allocate file size
-            if let Some(content_length) = response.content_length() {
-                if content_length == 0 {
-                    return Err(DownloadError::ZeroContentLength);
+            let wait_resumed = || async {
+                if let Some(mut pause) = pause.clone() {
+                    // if the sender was dropped, the download can't be resumed anymore, so we just continue
+                    let _ = pause.wait_for(|paused| !paused).await;
+                }
+            };
+            let acquire_request = || async {
+                wait_resumed().await;
+                if let Some(request_rate) = &request_rate {
+                    request_rate.acquire(1).await;
+                }
+            };
+
+            // Download into the part file, resuming from it if it exists.
+            // Return the actual digest if `checksum` is set, and whether the download was resumed.
+            let download = || async {
+                let resume_from = match tokio::fs::metadata(&part_path).await {
+                    Ok(metadata) => metadata.len(),
+                    Err(err) if err.kind() == ErrorKind::NotFound => 0,
+                    Err(err) => return Err(err.into()),
+                };
+
+                let request = client.get(url.clone());
+                let mut partial_response = None;
+                if resume_from > 0 {
+                    acquire_request().await;
+                    let response = request
+                        .try_clone()
+                        .expect("GET request has no body")
+                        .header(RANGE, format!("bytes={resume_from}-"))
+                        .send()
+                        .await?;
+                    let resumable = match response.status() {
+                        // the part file is not smaller than the remote file, e.g. the remote file was changed,
+                        // so we download from scratch
+                        StatusCode::RANGE_NOT_SATISFIABLE => false,
+                        // e.g. a proxy ignored the start of the range, appending it would corrupt the file
+                        StatusCode::PARTIAL_CONTENT => {
+                            content_range_start(&response) == Some(resume_from)
+                        }
+                        _ => true,
+                    };
+                    if resumable {
+                        partial_response = Some(response);
+                    }
                 }
+                let mut response = match partial_response {
+                    Some(response) => response,
+                    None => {
+                        acquire_request().await;
+                        request.send().await?
+                    }
+                }
+                .error_for_status()?;
+
+                let mut hasher = checksum.as_ref().map(|checksum| (checksum.new_hasher)());
+                let resumed = response.status() == StatusCode::PARTIAL_CONTENT;
+                let file = if resumed {
+                    let mut file = OpenOptions::new()
+                        .read(true)
+                        .append(true)
+                        .open(&part_path)
+                        .await?;
+                    // the resumed content must be hashed too
+                    if let Some(hasher) = &mut hasher {
+                        update_hasher(&mut file, hasher.as_mut()).await?;
+                    }
+                    file
+                } else {
+                    if response.content_length() == Some(0) {
+                        return Err(DownloadError::ZeroContentLength);
+                    }
+                    File::create(&part_path).await?
+                };
+                let mut file_buf = BufWriter::new(file);
 
-                file_buf
-                    .get_ref()
-                    .set_len(content_length)
-                    .await
-                    // if disk is full, this will fail
-                    .map_err(DownloadError::FileAllocationFailed)?;
-            }
+                if let Some(on_response) = &on_response {
+                    on_response(response.content_length());
+                }
 
-            while let Some(mut chunk) = response.chunk().await? {
-                let chunk_len: usize = chunk.len();
-                // may be we should check if occur `FileAllocationFailed` error
-                file_buf.write_all_buf(&mut chunk).await?;
+                loop {
+                    wait_resumed().await;
+                    let Some(mut chunk) = response.chunk().await? else {
+                        break;
+                    };
+                    let chunk_len: usize = chunk.len();
+                    if let Some(bandwidth) = &bandwidth {
+                        bandwidth.acquire(chunk_len.try_into().unwrap()).await;
+                    }
+                    if let Some(hasher) = &mut hasher {
+                        hasher.update(&chunk);
+                    }
+                    file_buf.write_all_buf(&mut chunk).await?;
 
-                if let Some(ref data_cursor) = data_cursor {
-                    if let Some(data_cursor) = data_cursor.upgrade() {
+                    for data_cursor in data_cursors.iter().filter_map(Weak::upgrade) {
                         let previous_value = data_cursor.fetch_add(chunk_len, Ordering::Release);
                         // or unstable `strict_add`
                         if previous_value.checked_add(chunk_len).is_none() {
                             panic!("Data cursor overflow");
                         }
                     }
+                    if let Some(on_data) = &on_data {
+                        on_data(chunk_len.try_into().unwrap());
+                    }
                 }
-            }
 
-            file_buf.flush().await?;
+                file_buf.flush().await?;
+                let file = file_buf.into_inner();
+                if fsync {
+                    file.sync_all().await?;
+                }
+                // close the file before renaming, which is required on Windows
+                drop(file);
+
+                let actual =
+                    hasher.map(|hasher| base16ct::lower::encode_string(&hasher.finalize()));
+                Ok::<_, DownloadError>((actual, resumed))
+            };
+            // Delete the part file if the `actual` digest mismatches the expected one.
+            let verify = |actual: Option<String>| async {
+                if let (Some(actual), Some(Checksum { expected, .. })) = (actual, &checksum) {
+                    if !actual.eq_ignore_ascii_case(expected) {
+                        tokio::fs::remove_file(&part_path).await?;
+                        return Err(DownloadError::ChecksumMismatch {
+                            expected: expected.clone(),
+                            actual,
+                        });
+                    }
+                }
+                Ok(())
+            };
+
+            let (actual, resumed) = download().await?;
+            match verify(actual).await {
+                // The existing part file may be stale or corrupt, e.g. left by an older version of the image.
+                // It has been deleted, so download from scratch once.
+                Err(DownloadError::ChecksumMismatch { .. }) if resumed => {
+                    let (actual, _) = download().await?;
+                    verify(actual).await?;
+                }
+                result => result?,
+            }
+            tokio::fs::rename(&part_path, &file_path).await?;
             Ok::<P, DownloadError>(file_path)
         }
     }
@@ -191,10 +526,97 @@ impl Downloader {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::sync::Arc;
+    use std::num::NonZeroU64;
+    use std::time::Duration;
+
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, Request, Respond, ResponseTemplate};
 
     const URL: &str = "https://httpbin.org/image/png";
     const FILE_NAME: &str = ".test.png";
+    const CONTENT: &[u8] = b"The quick brown fox jumps over the lazy dog";
+
+    /// Serve [`CONTENT`], and support `Range: bytes=N-` requests if `accept_ranges`.
+    ///
+    /// If `ignore_range_start`, the range requests are answered from the first byte,
+    /// like a misbehaving proxy.
+    struct RangeResponder {
+        accept_ranges: bool,
+        ignore_range_start: bool,
+    }
+
+    impl Respond for RangeResponder {
+        fn respond(&self, request: &Request) -> ResponseTemplate {
+            let len = CONTENT.len();
+            let start = request
+                .headers
+                .get(RANGE.as_str())
+                .filter(|_| self.accept_ranges)
+                .and_then(|range| {
+                    range
+                        .to_str()
+                        .ok()?
+                        .strip_prefix("bytes=")?
+                        .strip_suffix('-')?
+                        .parse::<usize>()
+                        .ok()
+                });
+            match start {
+                None if self.accept_ranges => ResponseTemplate::new(200)
+                    .insert_header("Accept-Ranges", "bytes")
+                    .set_body_bytes(CONTENT),
+                None => ResponseTemplate::new(200).set_body_bytes(CONTENT),
+                Some(_) if self.ignore_range_start => ResponseTemplate::new(206)
+                    .insert_header("Accept-Ranges", "bytes")
+                    .insert_header("Content-Range", format!("bytes 0-{}/{len}", len - 1))
+                    .set_body_bytes(CONTENT),
+                Some(start) if start >= len => ResponseTemplate::new(416)
+                    .insert_header("Content-Range", format!("bytes */{len}")),
+                Some(start) => ResponseTemplate::new(206)
+                    .insert_header("Accept-Ranges", "bytes")
+                    .insert_header("Content-Range", format!("bytes {start}-{}/{len}", len - 1))
+                    .set_body_bytes(&CONTENT[start..]),
+            }
+        }
+    }
+
+    async fn range_server(accept_ranges: bool) -> MockServer {
+        mock_server(RangeResponder {
+            accept_ranges,
+            ignore_range_start: false,
+        })
+        .await
+    }
+
+    async fn mock_server(responder: RangeResponder) -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(responder)
+            .mount(&server)
+            .await;
+        server
+    }
+
+    /// Download [`CONTENT`] from `server` with the existing `part` file,
+    /// return the downloaded bytes.
+    async fn resume_download(server: &MockServer, part: &[u8]) -> usize {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file_path = temp_dir.path().join(FILE_NAME);
+        std::fs::write(part_path(&file_path), part).unwrap();
+
+        let downloader = Downloader::session(Client::new(), temp_dir.path());
+        let data_cursor = Arc::new(AtomicUsize::new(0));
+        downloader
+            .future(server.uri(), FILE_NAME)
+            .add_data_cursor(Arc::downgrade(&data_cursor))
+            .build()
+            .await
+            .expect("Download failed");
+
+        assert_eq!(std::fs::read(&file_path).unwrap(), CONTENT);
+        assert!(!part_path(&file_path).exists());
+        data_cursor.load(Ordering::Acquire)
+    }
 
     #[tokio::test]
     async fn test_download() {
@@ -245,4 +667,165 @@ mod tests {
 
         temp_dir.close().unwrap();
     }
+
+    #[tokio::test]
+    async fn test_resume_download() {
+        let server = range_server(true).await;
+        let downloaded = resume_download(&server, &CONTENT[..10]).await;
+        assert_eq!(downloaded, CONTENT.len() - 10);
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests[0].headers.get("range").unwrap(), "bytes=10-");
+
+        // the part file is larger than the remote file, so download from scratch
+        let server = range_server(true).await;
+        let downloaded = resume_download(&server, &[b'x'; 100]).await;
+        assert_eq!(downloaded, CONTENT.len());
+    }
+
+    #[tokio::test]
+    async fn test_resume_download_fallback() {
+        // the server doesn't support range requests, so download from scratch
+        let server = range_server(false).await;
+        let downloaded = resume_download(&server, b"garbage").await;
+        assert_eq!(downloaded, CONTENT.len());
+
+        // the partial content doesn't start at the end of the part file, so download from scratch
+        let server = mock_server(RangeResponder {
+            accept_ranges: true,
+            ignore_range_start: true,
+        })
+        .await;
+        let downloaded = resume_download(&server, &CONTENT[..10]).await;
+        // `resume_download` checks the file, which would be corrupted if the partial content was appended
+        assert_eq!(downloaded, CONTENT.len());
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].headers.get("range").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_verify_checksum() {
+        // see: https://en.wikipedia.org/wiki/MD5#MD5_hashes
+        const MD5: &str = "9e107d9d372bb6826bd81d3542a419d6";
+
+        let server = range_server(true).await;
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file_path = temp_dir.path().join(FILE_NAME);
+        let downloader = Downloader::session(Client::new(), temp_dir.path());
+
+        // the resumed content is verified too
+        std::fs::write(part_path(&file_path), &CONTENT[..10]).unwrap();
+        downloader
+            .future(server.uri(), FILE_NAME)
+            .verify::<md5::Md5>(MD5)
+            .build()
+            .await
+            .expect("Download failed");
+        assert_eq!(std::fs::read(&file_path).unwrap(), CONTENT);
+        std::fs::remove_file(&file_path).unwrap();
+
+        let err = downloader
+            .future(server.uri(), FILE_NAME)
+            .verify::<md5::Md5>("d41d8cd98f00b204e9800998ecf8427e")
+            .build()
+            .await
+            .expect_err("checksum should mismatch");
+        assert!(matches!(err, DownloadError::ChecksumMismatch { actual, .. } if actual == MD5));
+        assert!(!file_path.exists());
+        assert!(!part_path(&file_path).exists());
+    }
+
+    #[tokio::test]
+    async fn test_verify_corrupt_part_file() {
+        const MD5: &str = "9e107d9d372bb6826bd81d3542a419d6";
+
+        let server = range_server(true).await;
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file_path = temp_dir.path().join(FILE_NAME);
+        let downloader = Downloader::session(Client::new(), temp_dir.path());
+
+        // e.g. left by an older version of the image
+        std::fs::write(part_path(&file_path), b"corrupted!").unwrap();
+        downloader
+            .future(server.uri(), FILE_NAME)
+            .verify::<md5::Md5>(MD5)
+            .build()
+            .await
+            .expect("the corrupt part file should be downloaded again");
+        assert_eq!(std::fs::read(&file_path).unwrap(), CONTENT);
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(requests[0].headers.get("range").unwrap(), "bytes=10-");
+        assert!(requests[1].headers.get("range").is_none());
+        std::fs::remove_file(&file_path).unwrap();
+
+        // a fresh download is not retried
+        let server = range_server(true).await;
+        let err = downloader
+            .future(server.uri(), FILE_NAME)
+            .verify::<md5::Md5>("d41d8cd98f00b204e9800998ecf8427e")
+            .build()
+            .await
+            .expect_err("checksum should mismatch");
+        assert!(matches!(err, DownloadError::ChecksumMismatch { .. }));
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_bandwidth() {
+        let server = range_server(true).await;
+        let temp_dir = tempfile::tempdir().unwrap();
+        let downloader = Downloader::session(Client::new(), temp_dir.path());
+        // the bucket holds 20 bytes, so the remaining 23 bytes take more than 1 second
+        let bandwidth = Arc::new(RateLimiter::new(NonZeroU64::new(20).unwrap()));
+
+        let start = tokio::time::Instant::now();
+        downloader
+            .future(server.uri(), FILE_NAME)
+            .bandwidth(bandwidth)
+            .build()
+            .await
+            .expect("Download failed");
+        assert!(start.elapsed() >= Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_request_rate() {
+        let server = range_server(true).await;
+        let temp_dir = tempfile::tempdir().unwrap();
+        let downloader = Downloader::session(Client::new(), temp_dir.path());
+        // the bucket holds 2 requests, so the third request waits for 0.5 second
+        let request_rate = Arc::new(RateLimiter::new(NonZeroU64::new(2).unwrap()));
+
+        let start = tokio::time::Instant::now();
+        for _ in 0..3 {
+            downloader
+                .future(server.uri(), FILE_NAME)
+                .request_rate(request_rate.clone())
+                .build()
+                .await
+                .expect("Download failed");
+        }
+        assert!(start.elapsed() >= Duration::from_millis(500));
+    }
+
+    #[tokio::test]
+    async fn test_write_atomic() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("tags.txt");
+
+        write_atomic(&path, "foo, bar", false).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "foo, bar");
+        write_atomic(&path, "baz", true).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "baz");
+        assert!(!part_path(&path).exists());
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_is_retryable() {
+        assert!(DownloadError::Io(ErrorKind::Interrupted.into()).is_retryable());
+        assert!(!DownloadError::Io(ErrorKind::PermissionDenied.into()).is_retryable());
+        assert!(!DownloadError::ZeroContentLength.is_retryable());
+        assert!(!DownloadError::FileAllocationFailed(ErrorKind::Other.into()).is_retryable());
+    }
 }
@@ -0,0 +1,452 @@
+//! Client-side filters to drop the unwanted posts before downloading.
+//!
+//! Usually, you set the [`PostFilter`] to [`crate::api::BatchGetter::filter`],
+//! and the [`Blacklist`] to [`crate::api::BatchGetter::blacklist`],
+//! so that `num_imgs` counts the posts that pass them.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde::Deserialize;
+use validator::{Validate, ValidationError};
+
+use crate::api::data::field::{Post, Rating};
+
+/// The reason why a post is rejected by [`PostFilter`].
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FilterReason {
+    /// See [`PostFilter::ratings`].
+    Rating,
+    /// See [`PostFilter::min_score`].
+    Score,
+    /// See [`PostFilter::min_width`] and [`PostFilter::max_width`].
+    Width,
+    /// See [`PostFilter::min_height`] and [`PostFilter::max_height`].
+    Height,
+    /// See [`PostFilter::min_aspect_ratio`].
+    AspectRatio,
+    /// See [`PostFilter::extensions`].
+    Extension,
+    /// See [`Blacklist`].
+    Blacklist,
+}
+
+impl FilterReason {
+    /// All the reasons, in the order of checking.
+    pub const ALL: [Self; 7] = [
+        Self::Rating,
+        Self::Score,
+        Self::Width,
+        Self::Height,
+        Self::AspectRatio,
+        Self::Extension,
+        Self::Blacklist,
+    ];
+
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Rating => "rating",
+            Self::Score => "score",
+            Self::Width => "width",
+            Self::Height => "height",
+            Self::AspectRatio => "aspect ratio",
+            Self::Extension => "extension",
+            Self::Blacklist => "blacklist",
+        }
+    }
+}
+
+impl fmt::Display for FilterReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The filter of the posts, which can be deserialized from the config.
+///
+/// All conditions are disabled by default.
+/// If a condition is enabled, but the booru doesn't return the metadata it needs,
+/// e.g. [`Post::rating`] is `None`, the post is rejected to be on the safe side.
+#[non_exhaustive]
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Validate)]
+#[serde(default)]
+#[validate(schema(function = "validate_ranges"))]
+pub struct PostFilter {
+    /// The allowed ratings, empty means all ratings are allowed.
+    pub ratings: Vec<Rating>,
+    /// The minimum score, inclusive.
+    pub min_score: Option<i64>,
+    /// The minimum width in pixels, inclusive.
+    pub min_width: Option<u64>,
+    /// The maximum width in pixels, inclusive.
+    pub max_width: Option<u64>,
+    /// The minimum height in pixels, inclusive.
+    pub min_height: Option<u64>,
+    /// The maximum height in pixels, inclusive.
+    pub max_height: Option<u64>,
+    /// The minimum `width / height`, inclusive, e.g. `1.0` rejects the portrait images.
+    ///
+    /// This field is validated to ensure it is positive.
+    #[validate(range(exclusive_min = 0.0, message = "min_aspect_ratio must be positive"))]
+    pub min_aspect_ratio: Option<f64>,
+    /// The allowed file extensions without the leading dot, e.g. `["jpg", "png"]`,
+    /// which are compared case-insensitively. Empty means all extensions are allowed.
+    pub extensions: Vec<String>,
+}
+
+fn validate_ranges(filter: &PostFilter) -> Result<(), ValidationError> {
+    let ranges = [
+        (filter.min_width, filter.max_width, "min_width > max_width"),
+        (
+            filter.min_height,
+            filter.max_height,
+            "min_height > max_height",
+        ),
+    ];
+    for (min, max, message) in ranges {
+        if let (Some(min), Some(max)) = (min, max) {
+            if min > max {
+                return Err(ValidationError::new("range").with_message(message.into()));
+            }
+        }
+    }
+    Ok(())
+}
+
+impl PostFilter {
+    /// Whether all conditions are disabled, i.e. all posts pass.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Check the `post` against the conditions in the order of [`FilterReason::ALL`].
+    ///
+    /// # Errors
+    ///
+    /// Return the first condition the `post` fails.
+    pub fn check(&self, post: &Post) -> Result<(), FilterReason> {
+        if !self.ratings.is_empty()
+            && !post
+                .rating
+                .is_some_and(|rating| self.ratings.contains(&rating))
+        {
+            return Err(FilterReason::Rating);
+        }
+        if let Some(min_score) = self.min_score {
+            if !post.score.is_some_and(|score| score >= min_score) {
+                return Err(FilterReason::Score);
+            }
+        }
+        if self.min_width.is_some() || self.max_width.is_some() {
+            let in_range = post.width.is_some_and(|width| {
+                self.min_width.map_or(true, |min| width >= min)
+                    && self.max_width.map_or(true, |max| width <= max)
+            });
+            if !in_range {
+                return Err(FilterReason::Width);
+            }
+        }
+        if self.min_height.is_some() || self.max_height.is_some() {
+            let in_range = post.height.is_some_and(|height| {
+                self.min_height.map_or(true, |min| height >= min)
+                    && self.max_height.map_or(true, |max| height <= max)
+            });
+            if !in_range {
+                return Err(FilterReason::Height);
+            }
+        }
+        if let Some(min_aspect_ratio) = self.min_aspect_ratio {
+            let size = post.width.zip(post.height);
+            let wide_enough = size.is_some_and(|(width, height)| {
+                height != 0 && width as f64 / height as f64 >= min_aspect_ratio
+            });
+            if !wide_enough {
+                return Err(FilterReason::AspectRatio);
+            }
+        }
+        if !self.extensions.is_empty() {
+            let extension = post.filename.extension().map(|ext| ext.to_string_lossy());
+            let allowed = extension.is_some_and(|extension| {
+                self.extensions.iter().any(|allowed| {
+                    allowed
+                        .trim_start_matches('.')
+                        .eq_ignore_ascii_case(&extension)
+                })
+            });
+            if !allowed {
+                return Err(FilterReason::Extension);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The blacklist of tags, the posts with any matching tag are rejected.
+///
+/// A pattern is either an exact tag, or contains `*` wildcards matching any characters,
+/// e.g. `*_censor*` matches `bar_censor` and `mosaic_censoring`.
+/// The patterns are lowercased, as the tags of the boorus are lowercase.
+///
+/// Unlike the `-tag` exclusion in the search tags, which is limited by the booru,
+/// the blacklist can be arbitrarily long, because it is checked client-side against [`Post::tags`].
+///
+/// It can be deserialized from a list of patterns.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "Vec<String>")]
+pub struct Blacklist {
+    exact: HashSet<String>,
+    wildcards: Vec<String>,
+}
+
+/// Whether `tag` matches the `pattern` with `*` wildcards.
+fn wildcard_match(pattern: &str, tag: &str) -> bool {
+    let mut parts = pattern.split('*');
+    // `split` always yields at least one part
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = tag.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts: Vec<_> = parts.collect();
+    // there is at least one `*`, so the last part is the suffix
+    let Some(last) = parts.pop() else {
+        return rest.is_empty();
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+impl Blacklist {
+    /// Compile the `patterns`.
+    ///
+    /// # Errors
+    ///
+    /// If any pattern is empty or contains whitespace, which can never match a tag.
+    pub fn new<I, S>(patterns: I) -> anyhow::Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut blacklist = Self::default();
+        for pattern in patterns {
+            let pattern = pattern.as_ref();
+            if pattern.is_empty() || pattern.contains(char::is_whitespace) {
+                return Err(anyhow::anyhow!(
+                    "Blacklist pattern cannot be empty or contain whitespace: {pattern:?}"
+                ));
+            }
+            let pattern = pattern.to_lowercase();
+            if pattern.contains('*') {
+                blacklist.wildcards.push(pattern);
+            } else {
+                blacklist.exact.insert(pattern);
+            }
+        }
+        Ok(blacklist)
+    }
+
+    /// Whether there is no pattern, i.e. all posts pass.
+    pub fn is_empty(&self) -> bool {
+        self.exact.is_empty() && self.wildcards.is_empty()
+    }
+
+    /// Whether the single `tag` matches any pattern.
+    pub fn matches(&self, tag: &str) -> bool {
+        self.exact.contains(tag)
+            || self
+                .wildcards
+                .iter()
+                .any(|pattern| wildcard_match(pattern, tag))
+    }
+
+    /// Check the [`Post::tags`] of `post`.
+    ///
+    /// # Errors
+    ///
+    /// Return [`FilterReason::Blacklist`] if any tag matches.
+    pub fn check(&self, post: &Post) -> Result<(), FilterReason> {
+        if post.tags.split_whitespace().any(|tag| self.matches(tag)) {
+            return Err(FilterReason::Blacklist);
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<Vec<String>> for Blacklist {
+    type Error = anyhow::Error;
+
+    fn try_from(patterns: Vec<String>) -> Result<Self, Self::Error> {
+        Self::new(patterns)
+    }
+}
+
+/// The number of posts rejected by [`PostFilter`] and [`Blacklist`] for each [`FilterReason`].
+///
+/// This is thread-safe, so it can be shared with the [`crate::api::BatchGetter::stream`].
+#[derive(Debug, Default)]
+pub struct FilterStats {
+    counts: [AtomicU64; FilterReason::ALL.len()],
+}
+
+impl FilterStats {
+    /// Count a post rejected for `reason`.
+    pub fn record(&self, reason: FilterReason) {
+        self.counts[reason as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The number of posts rejected for `reason`.
+    pub fn get(&self, reason: FilterReason) -> u64 {
+        self.counts[reason as usize].load(Ordering::Relaxed)
+    }
+
+    /// The total number of rejected posts.
+    pub fn total(&self) -> u64 {
+        FilterReason::ALL
+            .into_iter()
+            .map(|reason| self.get(reason))
+            .sum()
+    }
+
+    /// Iterate over the reasons which rejected at least one post, with their counts.
+    pub fn iter(&self) -> impl Iterator<Item = (FilterReason, u64)> + '_ {
+        FilterReason::ALL
+            .into_iter()
+            .map(|reason| (reason, self.get(reason)))
+            .filter(|(_, count)| *count > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    fn post(image: &str, width: u64, height: u64) -> Post {
+        let mut post = Post::new(
+            1,
+            String::new(),
+            String::new(),
+            String::new(),
+            PathBuf::from(image),
+        );
+        post.rating = Some(Rating::General);
+        post.score = Some(10);
+        post.width = Some(width);
+        post.height = Some(height);
+        post
+    }
+
+    #[test]
+    fn test_check() {
+        let filter = PostFilter::default();
+        assert!(filter.is_empty());
+        assert_eq!(filter.check(&post("a.jpg", 1, 1)), Ok(()));
+
+        let filter = PostFilter {
+            ratings: vec![Rating::General, Rating::Sensitive],
+            min_score: Some(5),
+            min_width: Some(1000),
+            max_height: Some(2000),
+            min_aspect_ratio: Some(1.0),
+            extensions: vec![String::from(".JPG"), String::from("png")],
+            ..Default::default()
+        };
+        filter.validate().unwrap();
+        assert_eq!(filter.check(&post("a.jpg", 1920, 1080)), Ok(()));
+        assert_eq!(filter.check(&post("a.png", 1000, 1000)), Ok(()));
+
+        let mut explicit = post("a.jpg", 1920, 1080);
+        explicit.rating = Some(Rating::Explicit);
+        assert_eq!(filter.check(&explicit), Err(FilterReason::Rating));
+        let mut unknown = post("a.jpg", 1920, 1080);
+        unknown.score = None;
+        assert_eq!(filter.check(&unknown), Err(FilterReason::Score));
+        assert_eq!(
+            filter.check(&post("a.jpg", 999, 999)),
+            Err(FilterReason::Width)
+        );
+        assert_eq!(
+            filter.check(&post("a.jpg", 4000, 2001)),
+            Err(FilterReason::Height)
+        );
+        assert_eq!(
+            filter.check(&post("a.jpg", 1080, 1920)),
+            Err(FilterReason::AspectRatio)
+        );
+        assert_eq!(
+            filter.check(&post("a.gif", 1920, 1080)),
+            Err(FilterReason::Extension)
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let filter = PostFilter {
+            min_width: Some(2),
+            max_width: Some(1),
+            ..Default::default()
+        };
+        filter
+            .validate()
+            .expect_err("min_width > max_width should be invalid");
+
+        let filter = PostFilter {
+            min_aspect_ratio: Some(0.0),
+            ..Default::default()
+        };
+        filter
+            .validate()
+            .expect_err("zero min_aspect_ratio should be invalid");
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("*_censor*", "bar_censor"));
+        assert!(wildcard_match("*_censor*", "mosaic_censoring"));
+        assert!(!wildcard_match("*_censor*", "censored"));
+        assert!(wildcard_match("*", "cat"));
+        assert!(wildcard_match("cat*", "cat"));
+        assert!(wildcard_match("c*t", "cat"));
+        assert!(!wildcard_match("c*t", "cats"));
+        assert!(wildcard_match("a*b*a", "aba"));
+        assert!(!wildcard_match("ab*ba", "aba"));
+    }
+
+    #[test]
+    fn test_blacklist() {
+        let blacklist = Blacklist::new(["Comic", "*_censor*"]).unwrap();
+        assert!(!blacklist.is_empty());
+        assert!(blacklist.matches("comic"));
+        assert!(!blacklist.matches("4koma_comic"));
+
+        let mut post = post("a.jpg", 1, 1);
+        post.tags = String::from("cat 1girl");
+        assert_eq!(blacklist.check(&post), Ok(()));
+        post.tags = String::from("cat bar_censor 1girl");
+        assert_eq!(blacklist.check(&post), Err(FilterReason::Blacklist));
+
+        assert!(Blacklist::new([""]).is_err());
+        assert!(Blacklist::new(["cat girl"]).is_err());
+    }
+
+    #[test]
+    fn test_stats() {
+        let stats = FilterStats::default();
+        stats.record(FilterReason::Score);
+        stats.record(FilterReason::Score);
+        stats.record(FilterReason::Extension);
+        assert_eq!(stats.get(FilterReason::Score), 2);
+        assert_eq!(stats.total(), 3);
+        assert_eq!(
+            stats.iter().collect::<Vec<_>>(),
+            [(FilterReason::Score, 2), (FilterReason::Extension, 1)]
+        );
+    }
+}
@@ -17,7 +17,7 @@
 //! ## As a library
 //!
 //! As a library, usually you prefer to use [`scheduler`]
-//! and [`api`] to download images from gelbooru api.
+//! and [`api`] to download images from booru api.
 //!
 //! See [`scheduler::Scheduler#example`] for example.
 //!
@@ -46,7 +46,8 @@
 //!
 //!     See [reqwest#optional-features] for details.
 //!
-//! - `cli`: Enable the command line utility.
+//! - `cli`: Enable the command line utility,
+//!   and the process bar [`progress::ProgressBarObserver`].
 //!
 //! [tls]: https://en.wikipedia.org/wiki/Transport_Layer_Security
 //! [`reqwest/default-tls`]: https://docs.rs/reqwest/0.12/reqwest/tls/index.html#default-tls
@@ -76,5 +77,9 @@ pub mod scheduler;
 
 pub mod config;
 pub mod download;
+pub mod filter;
 pub mod hash;
+pub mod progress;
+pub mod rate_limit;
+pub mod retry;
 pub mod tool;
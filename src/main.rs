@@ -1,15 +1,21 @@
+use std::pin::pin;
 use std::process::ExitCode;
+use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::Context;
+use futures::TryStreamExt;
 use indicatif::{ProgressBar, ProgressFinish, ProgressStyle};
 use reqwest::Client;
 use tokio::runtime::Runtime;
 use tokio::signal;
+use tokio_util::sync::CancellationToken;
 
 use booru_dl::api::BatchGetter;
-use booru_dl::cli::{Cli, CommandFactory, Parser};
+use booru_dl::cli::{exit_code, Cli, CommandFactory, Parser};
 use booru_dl::config::Config;
+use booru_dl::filter::FilterStats;
+use booru_dl::progress::ProgressBarObserver;
 use booru_dl::scheduler::Scheduler;
 
 const SPINNER_FINISH_MODE: ProgressFinish = ProgressFinish::AndClear;
@@ -40,39 +46,117 @@ fn build_client(timeout: u64) -> reqwest::Result<Client> {
     client_builder.build()
 }
 
+/// Print the `err` like returning it from `main`, and exit with `code`.
 #[inline]
-async fn async_main(config: Config) -> anyhow::Result<()> {
+fn exit_with_error(err: anyhow::Error, code: u8) -> ExitCode {
+    eprintln!("Error: {err:?}");
+    ExitCode::from(code)
+}
+
+/// Print how many posts were rejected by each filter, if any.
+fn print_filter_stats(filter_stats: &FilterStats) {
+    let total = filter_stats.total();
+    if total == 0 {
+        return;
+    }
+    let details: Vec<_> = filter_stats
+        .iter()
+        .map(|(reason, count)| format!("{reason}: {count}"))
+        .collect();
+    println!("{total} posts were filtered out ({})", details.join(", "));
+}
+
+#[inline]
+async fn async_main(config: Config, cancel_token: CancellationToken) -> anyhow::Result<ExitCode> {
     let client = build_client(config.timeout).context("failed to build reqwest client")?;
 
+    let booru = config.booru();
+
+    let filter_stats = FilterStats::default();
     // Because `config` and `cli` modules have already validated the config, we can safely unwrap here.
-    let getter = BatchGetter::build(&client, &config.tags, config.num_imgs.get())
-        .expect("wrong config parser, please raise an issue on GitHub");
+    let getter = BatchGetter::build(&client, &booru, &config.tags, config.num_imgs.get())
+        .expect("wrong config parser, please raise an issue on GitHub")
+        .concurrency(config.api_concurrency)
+        .filter(&config.filter)
+        .blacklist(&config.blacklist)
+        .filter_stats(&filter_stats);
+
+    // We only wait for the first page here, the rest pages are downloaded as soon as they arrive.
+    let mut api_post_stream = pin!(getter.stream());
 
     let spinner = build_spinner();
-    spinner.set_message("Fetching image data from Gelbooru API...");
+    spinner.set_message(format!("Fetching image data from {} API...", config.site));
     spinner.enable_steady_tick(Duration::from_secs_f32(SPINNER_TICK_SECS));
-    let api_post_data = getter.run().await.context("failed to get data from API")?;
+    let api_post_data = tokio::select! {
+        api_post_data = api_post_stream.try_next() => api_post_data,
+        () = cancel_token.cancelled() => return Ok(ExitCode::from(exit_code::INTERRUPTED)),
+    };
+    let api_post_data = match api_post_data {
+        Ok(api_post_data) => api_post_data,
+        Err(err) => {
+            let err = anyhow::Error::new(err).context("failed to get data from API");
+            return Ok(exit_with_error(err, exit_code::API_ERROR));
+        }
+    };
     spinner.finish_with_message("Image data fetched successfully!");
 
-    // HACK: This is not considered an error, so we just return Ok(()).
-    if api_post_data.is_empty() {
+    // HACK: This is not considered an error, so we just return `SUCCESS`.
+    let Some(api_post_data) = api_post_data else {
+        print_filter_stats(&filter_stats);
         println!(
             "There is no image found with the given tags: {}",
             config.tags
         );
-        return Ok(());
-    }
+        return Ok(ExitCode::SUCCESS);
+    };
 
-    let scheduler = Scheduler::build(client, config.download_dir, api_post_data)
+    let download_retry_policy = config.download_retry_policy();
+    let scheduler = Scheduler::build(client.clone(), config.download_dir, api_post_data)
         .await
-        .context("Unable to ensure the existence of the download directory")?;
-
-    scheduler.launch().await;
+        .context("Unable to ensure the existence of the download directory")?
+        .retry(download_retry_policy)
+        .fsync(config.fsync)
+        .download_concurrency(config.download_concurrency);
+    let scheduler = match config.hash_concurrency {
+        Some(hash_concurrency) => scheduler.hash_concurrency(hash_concurrency),
+        None => scheduler,
+    };
+    let scheduler = match config.max_bytes_per_sec {
+        Some(max_bytes_per_sec) => scheduler.max_bytes_per_sec(max_bytes_per_sec),
+        None => scheduler,
+    };
+    let scheduler = match config.download_requests_per_sec {
+        Some(download_requests_per_sec) => {
+            scheduler.max_requests_per_sec(download_requests_per_sec)
+        }
+        None => scheduler,
+    };
 
-    Ok(())
+    let report = scheduler
+        .observer(Arc::new(ProgressBarObserver::new()))
+        .cancel_token(cancel_token)
+        .launch_stream(api_post_stream)
+        .await;
+    print_filter_stats(&filter_stats);
+    if report.cancelled {
+        return Ok(ExitCode::from(exit_code::INTERRUPTED));
+    }
+    let failed = report.failed();
+    // The API error takes precedence, because the posts after it are not even tried.
+    if let Some(err) = report.api_error {
+        let err = anyhow::Error::new(err).context("failed to get data from API");
+        return Ok(exit_with_error(err, exit_code::API_ERROR));
+    }
+    if failed == 0 {
+        Ok(ExitCode::SUCCESS)
+    } else if failed == report.posts.len() {
+        Ok(ExitCode::from(exit_code::ALL_FAILED))
+    } else {
+        Ok(ExitCode::from(exit_code::SOME_FAILED))
+    }
 }
 
-fn main() -> anyhow::Result<ExitCode> {
+fn main() -> ExitCode {
     // here, if parse fails, the program will be `abort`ed, and no `Drop` will be called,
     // but it's okay, because we don't need to clean up anything.
     let cli = Cli::parse();
@@ -84,22 +168,36 @@ fn main() -> anyhow::Result<ExitCode> {
             // if we can't get the config from the editor, we drop the whole program.
             Err(err) => {
                 let _ = err.print();
-                return Ok(ExitCode::from(u8::try_from(err.exit_code()).unwrap()));
+                return ExitCode::from(exit_code::CONFIG_ERROR);
             }
         },
     };
 
-    let runtime = Runtime::new().context("failed to build tokio runtime")?;
-    runtime.block_on(async {
+    let runtime = match Runtime::new().context("failed to build tokio runtime") {
+        Ok(runtime) => runtime,
+        Err(err) => return exit_with_error(err, exit_code::ERROR),
+    };
+    let result = runtime.block_on(async {
+        let cancel_token = CancellationToken::new();
+        // The first Ctrl-C cancels the downloads gracefully, and the second one exits immediately.
+        let force_exit = async {
+            signal::ctrl_c()
+                .await
+                .expect("failed to listen for ctrl-c signal");
+            eprintln!(
+                "Ctrl-C received, waiting up to {}s for the in-flight downloads, press Ctrl-C again to exit immediately...",
+                Scheduler::DEFAULT_GRACE_PERIOD.as_secs()
+            );
+            cancel_token.cancel();
+            signal::ctrl_c()
+                .await
+                .expect("failed to listen for ctrl-c signal");
+            eprintln!("Ctrl-C received again, exiting...");
+        };
         tokio::select! {
-            result = async_main(config) => {result},
-            result = signal::ctrl_c() => {
-                result.expect("failed to listen for ctrl-c signal");
-                println!("Ctrl-C received, exiting...");
-                Ok(())
-            },
+            result = async_main(config, cancel_token.clone()) => {result},
+            () = force_exit => Ok(ExitCode::from(exit_code::INTERRUPTED)),
         }
-    })?;
-
-    Ok(ExitCode::SUCCESS)
+    });
+    result.unwrap_or_else(|err| exit_with_error(err, exit_code::ERROR))
 }
@@ -0,0 +1,232 @@
+//! A process bar implementation of [`ProgressObserver`] with [`indicatif`].
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use indicatif::style::ProgressTracker;
+use indicatif::{HumanBytes, ProgressBar, ProgressFinish, ProgressState, ProgressStyle};
+
+use super::ProgressObserver;
+use crate::scheduler::{DownloadOutcome, DownloadReport, PostReport};
+
+const PB_FINISH_MODE: ProgressFinish = ProgressFinish::Abandon;
+const PB_TICK_SECS: u64 = 1;
+/// The time interval for updating the download speed.
+const SPEED_UPDATE_SECS: u64 = 1;
+
+/// current download number status
+#[derive(Default)]
+struct DownloadStatus {
+    /// the number of files that have been downloaded successfully
+    done: u64,
+    // the number of files that already,which means no need to download
+    existed: u64,
+    // the number of files that failed to download
+    failed: u64,
+    // the number of files that were cancelled
+    cancelled: u64,
+    // the number of retries of all downloads
+    retried: u64,
+    // whether the downloads are paused
+    paused: bool,
+}
+
+/// The `{speed}` template key of the process bar,
+/// which is the average download speed of the last `SPEED_UPDATE_SECS` seconds.
+#[derive(Clone)]
+struct SpeedTracker {
+    /// The total bytes written, see [`ProgressObserver::on_bytes`].
+    bytes: Arc<AtomicU64>,
+    /// The instant and `bytes` of the last update.
+    last: Option<(Instant, u64)>,
+    /// In bytes per second.
+    speed: u64,
+}
+
+impl ProgressTracker for SpeedTracker {
+    fn clone_box(&self) -> Box<dyn ProgressTracker> {
+        Box::new(self.clone())
+    }
+
+    fn tick(&mut self, _: &ProgressState, now: Instant) {
+        let bytes = self.bytes.load(Ordering::Relaxed);
+        match self.last {
+            Some((last_instant, last_bytes)) => {
+                let elapsed = now.saturating_duration_since(last_instant);
+                if elapsed >= Duration::from_secs(SPEED_UPDATE_SECS) {
+                    // `f64` is precise enough for displaying
+                    self.speed = ((bytes - last_bytes) as f64 / elapsed.as_secs_f64()) as u64;
+                    self.last = Some((now, bytes));
+                }
+            }
+            // ignore previous data
+            None => self.last = Some((now, bytes)),
+        }
+    }
+
+    fn reset(&mut self, _: &ProgressState, _: Instant) {
+        self.last = None;
+        self.speed = 0;
+    }
+
+    fn write(&self, _: &ProgressState, w: &mut dyn std::fmt::Write) {
+        let _ = write!(w, "[{}/S]", HumanBytes(self.speed));
+    }
+}
+
+/// The observer which displays a process bar in the terminal with [`indicatif`],
+/// showing the download status and speed.
+///
+/// The failed downloads are printed to stderr above the process bar.
+pub struct ProgressBarObserver {
+    process_bar: ProgressBar,
+    status: Mutex<DownloadStatus>,
+    bytes: Arc<AtomicU64>,
+}
+
+impl ProgressBarObserver {
+    /// Create the observer, and display the process bar immediately.
+    pub fn new() -> Self {
+        let observer = Self::with_process_bar(ProgressBar::new(0));
+        observer
+            .process_bar
+            .enable_steady_tick(Duration::from_secs(PB_TICK_SECS));
+        observer
+    }
+
+    /// Apply the custom style to `process_bar`.
+    fn with_process_bar(process_bar: ProgressBar) -> Self {
+        // see: https://docs.rs/indicatif/latest/indicatif/#templates
+        const PROCESS_CHARS: &str = "#>-";
+        // `speed` for speed, `msg` for download status
+        const TEMPLATE: &str = "[{elapsed_precise}] {speed} [{wide_bar:.cyan/blue}] {msg} {human_pos}/{human_len} ({eta})";
+
+        let bytes = Arc::new(AtomicU64::new(0));
+        let style = ProgressStyle::with_template(TEMPLATE)
+            .unwrap()
+            .progress_chars(PROCESS_CHARS)
+            .with_key(
+                "speed",
+                SpeedTracker {
+                    bytes: bytes.clone(),
+                    last: None,
+                    speed: 0,
+                },
+            );
+        let status = DownloadStatus::default();
+        Self {
+            process_bar: process_bar
+                .with_style(style)
+                .with_message(Self::pb_msg(&status))
+                .with_finish(PB_FINISH_MODE),
+            status: Mutex::new(status),
+            bytes,
+        }
+    }
+
+    /// Return the formatted download status message
+    #[inline]
+    fn pb_msg(status: &DownloadStatus) -> String {
+        let DownloadStatus {
+            done,
+            existed,
+            failed,
+            cancelled,
+            retried,
+            paused,
+        } = status;
+        let paused = if *paused { "[PAUSED] " } else { "" };
+        format!("{paused}[done:{done}\texisted:{existed}\tfailed:{failed}\tcancelled:{cancelled}\tretried:{retried}]")
+    }
+}
+
+impl Default for ProgressBarObserver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProgressObserver for ProgressBarObserver {
+    fn on_task_queued(&self, _: u64) {
+        self.process_bar.inc_length(1);
+    }
+
+    fn on_bytes(&self, _: u64, bytes: u64) {
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    fn on_pause(&self) {
+        let mut status = self.status.lock().expect("status was poisoned");
+        status.paused = true;
+        self.process_bar.set_message(Self::pb_msg(&status));
+    }
+
+    fn on_resume(&self) {
+        let mut status = self.status.lock().expect("status was poisoned");
+        status.paused = false;
+        self.process_bar.set_message(Self::pb_msg(&status));
+    }
+
+    fn on_task_done(&self, report: &PostReport) {
+        let mut status = self.status.lock().expect("status was poisoned");
+        status.retried += u64::from(report.retries);
+        match &report.outcome {
+            DownloadOutcome::Done => status.done += 1,
+            DownloadOutcome::Existed => status.existed += 1,
+            // why `suspend`: https://docs.rs/indicatif/0.17.8/indicatif/struct.ProgressBar.html#method.suspend
+            // why `{:#}`: https://docs.rs/anyhow/1.0.86/anyhow/struct.Error.html#display-representations
+            DownloadOutcome::Failed(err) => {
+                status.failed += 1;
+                self.process_bar.suspend(|| eprintln!("{:#}", err));
+            }
+            DownloadOutcome::Cancelled => status.cancelled += 1,
+        }
+        self.process_bar.set_message(Self::pb_msg(&status));
+        self.process_bar.inc(1);
+    }
+
+    fn on_finish(&self, _: &DownloadReport) {
+        self.process_bar.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    use indicatif::ProgressDrawTarget;
+
+    #[test]
+    fn test_process_bar_observer() {
+        let observer = ProgressBarObserver::with_process_bar(ProgressBar::with_draw_target(
+            Some(0),
+            ProgressDrawTarget::hidden(),
+        ));
+        observer.on_task_queued(1);
+        observer.on_task_queued(2);
+        observer.on_bytes(1, 42);
+        assert_eq!(observer.process_bar.length(), Some(2));
+        assert_eq!(observer.bytes.load(Ordering::Relaxed), 42);
+
+        observer.on_task_done(&PostReport {
+            id: 1,
+            path: PathBuf::from("1.jpg"),
+            outcome: DownloadOutcome::Done,
+            bytes: 42,
+            retries: 1,
+            elapsed: Duration::ZERO,
+        });
+        assert_eq!(observer.process_bar.position(), 1);
+        // the tabs are expanded by `indicatif`
+        let message = observer.process_bar.message();
+        assert!(message.contains("done:1") && message.contains("retried:1"));
+
+        observer.on_pause();
+        assert!(observer.process_bar.message().starts_with("[PAUSED]"));
+        observer.on_resume();
+        assert!(!observer.process_bar.message().starts_with("[PAUSED]"));
+    }
+}
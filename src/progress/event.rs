@@ -0,0 +1,245 @@
+//! An event channel implementation of [`ProgressObserver`] with [`tokio::sync::broadcast`].
+
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use tokio::sync::broadcast;
+
+use super::ProgressObserver;
+use crate::scheduler::{DownloadOutcome, PostReport};
+
+/// The lifecycle event of a download task, sent by [`EventObserver`].
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchedulerEvent {
+    /// The task was arranged.
+    Queued {
+        /// The [`Post::id`](crate::api::data::field::Post::id).
+        id: u64,
+    },
+    /// The image doesn't exist, and starts downloading.
+    Started {
+        /// The [`Post::id`](crate::api::data::field::Post::id).
+        id: u64,
+    },
+    /// A chunk was written.
+    Progress {
+        /// The [`Post::id`](crate::api::data::field::Post::id).
+        id: u64,
+        /// The bytes written by the current response, which restarts from zero when retrying.
+        bytes: u64,
+        /// The content length of the current response, `None` if unknown.
+        total: Option<u64>,
+    },
+    /// The image already existed, so it was skipped.
+    Skipped {
+        /// The [`Post::id`](crate::api::data::field::Post::id).
+        id: u64,
+    },
+    /// The image was downloaded and the tags were written successfully.
+    Finished {
+        /// The [`Post::id`](crate::api::data::field::Post::id).
+        id: u64,
+        /// See [`PostReport::path`].
+        path: PathBuf,
+    },
+    /// Failed to check, download or write the tags.
+    Failed {
+        /// The [`Post::id`](crate::api::data::field::Post::id).
+        id: u64,
+        /// The error message with the causes, i.e. formatted with `{:#}`.
+        error: String,
+    },
+    /// The downloads were paused, see [`crate::scheduler::SchedulerHandle::pause`].
+    Paused,
+    /// The downloads were resumed, see [`crate::scheduler::SchedulerHandle::resume`].
+    Resumed,
+    /// The task was cancelled, see [`crate::scheduler::Scheduler::cancel_token`].
+    Cancelled {
+        /// The [`Post::id`](crate::api::data::field::Post::id).
+        id: u64,
+    },
+}
+
+/** The observer which sends [`SchedulerEvent`]s through a [`broadcast`] channel.
+
+The channel is closed when the observer is dropped,
+i.e. after [`crate::scheduler::Scheduler::launch`] returns, unless you keep a clone of the [`Arc`](std::sync::Arc).
+
+Sending never blocks the downloads, so a slow receiver may lag behind
+and miss the oldest events, see [`broadcast::error::RecvError::Lagged`].
+
+# Example
+
+```no_run
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+
+use reqwest::Client;
+use booru_dl::progress::EventObserver;
+use booru_dl::scheduler::Scheduler;
+
+#[tokio::main]
+async fn main() {
+    let (observer, mut receiver) = EventObserver::new(NonZeroUsize::new(1024).unwrap());
+    let scheduler = Scheduler::build(Client::new(), "download_dir", Vec::new())
+        .await
+        .unwrap()
+        .observer(Arc::new(observer));
+
+    let printer = tokio::spawn(async move {
+        while let Ok(event) = receiver.recv().await {
+            println!("{event:?}");
+        }
+    });
+    scheduler.launch().await;
+    printer.await.unwrap();
+}
+```
+*/
+#[derive(Debug)]
+pub struct EventObserver {
+    sender: broadcast::Sender<SchedulerEvent>,
+    /// The bytes written and the content length of the current response of the downloading posts.
+    progress: Mutex<HashMap<u64, (u64, Option<u64>)>>,
+}
+
+impl EventObserver {
+    /// Create the observer and a receiver, the channel can hold `capacity` events.
+    pub fn new(capacity: NonZeroUsize) -> (Self, broadcast::Receiver<SchedulerEvent>) {
+        let (sender, receiver) = broadcast::channel(capacity.get());
+        let observer = Self {
+            sender,
+            progress: Mutex::new(HashMap::new()),
+        };
+        (observer, receiver)
+    }
+
+    /// Create another receiver, which receives the events sent after this call.
+    pub fn subscribe(&self) -> broadcast::Receiver<SchedulerEvent> {
+        self.sender.subscribe()
+    }
+
+    #[inline]
+    fn send(&self, event: SchedulerEvent) {
+        // it's fine that there is no receiver
+        let _ = self.sender.send(event);
+    }
+}
+
+impl ProgressObserver for EventObserver {
+    fn on_task_queued(&self, id: u64) {
+        self.send(SchedulerEvent::Queued { id });
+    }
+
+    fn on_task_start(&self, id: u64) {
+        self.send(SchedulerEvent::Started { id });
+    }
+
+    fn on_response(&self, id: u64, content_length: Option<u64>) {
+        let mut progress = self.progress.lock().expect("progress was poisoned");
+        progress.insert(id, (0, content_length));
+    }
+
+    fn on_bytes(&self, id: u64, bytes: u64) {
+        let mut progress = self.progress.lock().expect("progress was poisoned");
+        let (written, total) = progress.entry(id).or_default();
+        *written += bytes;
+        self.send(SchedulerEvent::Progress {
+            id,
+            bytes: *written,
+            total: *total,
+        });
+    }
+
+    fn on_pause(&self) {
+        self.send(SchedulerEvent::Paused);
+    }
+
+    fn on_resume(&self) {
+        self.send(SchedulerEvent::Resumed);
+    }
+
+    fn on_task_done(&self, report: &PostReport) {
+        let id = report.id;
+        self.progress
+            .lock()
+            .expect("progress was poisoned")
+            .remove(&id);
+        let event = match &report.outcome {
+            DownloadOutcome::Done => SchedulerEvent::Finished {
+                id,
+                path: report.path.clone(),
+            },
+            DownloadOutcome::Existed => SchedulerEvent::Skipped { id },
+            DownloadOutcome::Failed(err) => SchedulerEvent::Failed {
+                id,
+                error: format!("{err:#}"),
+            },
+            DownloadOutcome::Cancelled => SchedulerEvent::Cancelled { id },
+        };
+        self.send(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    #[test]
+    fn test_event_observer() {
+        let (observer, mut receiver) = EventObserver::new(NonZeroUsize::new(16).unwrap());
+        observer.on_task_queued(1);
+        observer.on_task_start(1);
+        observer.on_response(1, Some(10));
+        observer.on_bytes(1, 4);
+        observer.on_bytes(1, 6);
+        // retrying restarts the progress
+        observer.on_response(1, None);
+        observer.on_bytes(1, 10);
+        observer.on_task_done(&PostReport {
+            id: 1,
+            path: PathBuf::from("1.jpg"),
+            outcome: DownloadOutcome::Done,
+            bytes: 20,
+            retries: 1,
+            elapsed: Duration::ZERO,
+        });
+        drop(observer);
+
+        let mut events = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            events.push(event);
+        }
+        assert_eq!(
+            events,
+            [
+                SchedulerEvent::Queued { id: 1 },
+                SchedulerEvent::Started { id: 1 },
+                SchedulerEvent::Progress {
+                    id: 1,
+                    bytes: 4,
+                    total: Some(10)
+                },
+                SchedulerEvent::Progress {
+                    id: 1,
+                    bytes: 10,
+                    total: Some(10)
+                },
+                SchedulerEvent::Progress {
+                    id: 1,
+                    bytes: 10,
+                    total: None
+                },
+                SchedulerEvent::Finished {
+                    id: 1,
+                    path: PathBuf::from("1.jpg")
+                },
+            ]
+        );
+    }
+}
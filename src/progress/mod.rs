@@ -0,0 +1,86 @@
+//! Observe the download progress of [`crate::scheduler::Scheduler`].
+//!
+//! See [`ProgressObserver`] for more information.
+//!
+//! [`EventObserver`] sends the progress as [`SchedulerEvent`]s through a channel,
+//! and with the `cli` feature, [`ProgressBarObserver`] displays a process bar in the terminal.
+
+use crate::scheduler::{DownloadReport, PostReport};
+
+#[cfg(feature = "cli")]
+mod bar;
+mod event;
+
+#[cfg(feature = "cli")]
+pub use bar::ProgressBarObserver;
+pub use event::{EventObserver, SchedulerEvent};
+
+/** The observer of the download progress, which is called by [`crate::scheduler::Scheduler`].
+
+All methods do nothing by default, so you only need to implement what you are interested in.
+The methods are called from the download tasks concurrently, so they should return quickly,
+e.g. update an atomic counter or send a message, instead of doing I/O.
+
+# Example
+
+```rust
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use booru_dl::progress::ProgressObserver;
+
+#[derive(Default)]
+struct BytesCounter(AtomicU64);
+
+impl ProgressObserver for BytesCounter {
+    fn on_bytes(&self, _id: u64, bytes: u64) {
+        self.0.fetch_add(bytes, Ordering::Relaxed);
+    }
+}
+```
+*/
+pub trait ProgressObserver: Send + Sync {
+    /// Called when the download task of post `id` is arranged.
+    fn on_task_queued(&self, id: u64) {
+        let _ = id;
+    }
+
+    /// Called when the post `id` starts downloading, i.e. it doesn't exist and
+    /// the download concurrency permits. This is called only once, even if the download is retried.
+    fn on_task_start(&self, id: u64) {
+        let _ = id;
+    }
+
+    /// Called when the response of post `id` arrives with `content_length`,
+    /// i.e. the remaining length if the download is resumed, or `None` if unknown.
+    /// This is called again for each retry.
+    fn on_response(&self, id: u64, content_length: Option<u64>) {
+        let _ = (id, content_length);
+    }
+
+    /// Called every time a chunk of `bytes` of post `id` is written, including the retries.
+    fn on_bytes(&self, id: u64, bytes: u64) {
+        let _ = (id, bytes);
+    }
+
+    /// Called when the downloads are paused, see [`crate::scheduler::SchedulerHandle::pause`].
+    fn on_pause(&self) {}
+
+    /// Called when the downloads are resumed, see [`crate::scheduler::SchedulerHandle::resume`].
+    fn on_resume(&self) {}
+
+    /// Called when the task of a post is completed, no matter it succeeded or not.
+    fn on_task_done(&self, report: &PostReport) {
+        let _ = report;
+    }
+
+    /// Called when all tasks are completed.
+    fn on_finish(&self, report: &DownloadReport) {
+        let _ = report;
+    }
+}
+
+/// The observer which does nothing, it's the default of [`crate::scheduler::Scheduler`].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoopObserver;
+
+impl ProgressObserver for NoopObserver {}
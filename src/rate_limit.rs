@@ -0,0 +1,102 @@
+//! A token-bucket rate limiter, e.g. to limit the download bandwidth.
+//!
+//! Usually, you don't need to use this module directly.
+//! [`crate::scheduler::Scheduler::max_bytes_per_sec`] will create a [`RateLimiter`] for you.
+
+use std::num::NonZeroU64;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+/// The state of the token bucket.
+#[derive(Debug)]
+struct Bucket {
+    /// The available tokens, negative means the tokens are borrowed in advance.
+    tokens: f64,
+    /// The last time the tokens were refilled.
+    last_refill: Instant,
+}
+
+/** A token-bucket rate limiter, which is shared through [`Arc`](std::sync::Arc).
+
+The bucket is refilled with `rate` tokens per second, and holds at most `rate` tokens,
+i.e. it allows a burst of one second.
+
+If there are not enough tokens, [`RateLimiter::acquire`] borrows them in advance and
+waits until the debt is paid off, so a large acquisition never starves,
+and the following acquisitions will wait for it.
+
+# Example
+
+```rust
+use std::num::NonZeroU64;
+
+use booru_dl::rate_limit::RateLimiter;
+
+#[tokio::main]
+async fn main() {
+    // 1 MiB/s
+    let limiter = RateLimiter::new(NonZeroU64::new(1024 * 1024).unwrap());
+    limiter.acquire(1024).await;
+}
+```
+*/
+#[derive(Debug)]
+pub struct RateLimiter {
+    rate: f64,
+    bucket: Mutex<Bucket>,
+}
+
+impl RateLimiter {
+    /// Create a rate limiter which allows `rate` tokens per second, the bucket is full initially.
+    pub fn new(rate: NonZeroU64) -> Self {
+        // `f64` is precise enough for any practical rate
+        let rate = rate.get() as f64;
+        Self {
+            rate,
+            bucket: Mutex::new(Bucket {
+                tokens: rate,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    /// Acquire `tokens` from the bucket, wait if there are not enough tokens.
+    pub async fn acquire(&self, tokens: u64) {
+        let wait = {
+            let mut bucket = self.bucket.lock().expect("rate limiter was poisoned");
+            let now = Instant::now();
+            let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+            bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.rate);
+            bucket.last_refill = now;
+
+            bucket.tokens -= tokens as f64;
+            if bucket.tokens >= 0.0 {
+                return;
+            }
+            Duration::from_secs_f64(-bucket.tokens / self.rate)
+        };
+        tokio::time::sleep(wait).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_acquire() {
+        let limiter = RateLimiter::new(NonZeroU64::new(1000).unwrap());
+        let start = Instant::now();
+
+        // the bucket is full initially
+        limiter.acquire(1000).await;
+        assert!(start.elapsed() < Duration::from_millis(100));
+
+        // borrow in advance, and the following acquisition waits for the debt
+        limiter.acquire(200).await;
+        limiter.acquire(100).await;
+        assert!(start.elapsed() >= Duration::from_millis(300));
+    }
+}
@@ -0,0 +1,259 @@
+//! The retry policy for transient errors of HTTP requests.
+//!
+//! Usually, you don't need to use this module directly.
+//! The [`crate::api`] backends will retry the API requests with [`RetryPolicy`] for you.
+
+use std::num::NonZeroU32;
+use std::time::{Duration, SystemTime};
+
+use rand::Rng;
+use reqwest::header::RETRY_AFTER;
+use reqwest::{RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+
+use crate::rate_limit::RateLimiter;
+
+/// A Consuming-Builders style retry policy with exponential backoff.
+///
+/// The `n`-th retry waits `base_delay * 2^n`, which is capped at `max_delay`.
+/// If `jitter` is enabled, the delay is randomly picked from `[delay / 2, delay]`,
+/// so that the concurrent requests don't retry at the same time.
+///
+/// If the server responds `429 Too Many Requests` or `503 Service Unavailable` with
+/// a `Retry-After` header, it is honored instead (still capped at `max_delay`).
+///
+/// # Example
+///
+/// ```rust
+/// use std::num::NonZeroU32;
+/// use std::time::Duration;
+///
+/// use booru_dl::retry::RetryPolicy;
+///
+/// let policy = RetryPolicy::new(NonZeroU32::new(5).unwrap(), Duration::from_secs(1))
+///     .max_delay(Duration::from_secs(10))
+///     .jitter(false);
+/// assert_eq!(policy.backoff(0), Duration::from_secs(1));
+/// assert_eq!(policy.backoff(3), Duration::from_secs(8));
+/// assert_eq!(policy.backoff(4), Duration::from_secs(10));
+/// ```
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The maximum number of attempts, including the first one.
+    pub max_attempts: NonZeroU32,
+    /// The delay before the first retry.
+    pub base_delay: Duration,
+    /// The maximum delay between two attempts.
+    pub max_delay: Duration,
+    /// Whether to randomize the delay.
+    pub jitter: bool,
+}
+
+impl RetryPolicy {
+    /// The default policy, which makes 3 attempts, starting from 500ms delay.
+    pub const DEFAULT: Self = Self {
+        max_attempts: match NonZeroU32::new(3) {
+            Some(max_attempts) => max_attempts,
+            None => unreachable!(),
+        },
+        base_delay: Duration::from_millis(500),
+        max_delay: Duration::from_secs(30),
+        jitter: true,
+    };
+
+    /// Create a policy with [`Self::DEFAULT`] `max_delay` and `jitter`.
+    pub fn new(max_attempts: NonZeroU32, base_delay: Duration) -> Self {
+        Self {
+            max_attempts,
+            base_delay,
+            ..Self::DEFAULT
+        }
+    }
+
+    /// Set the maximum delay between two attempts.
+    pub fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Set whether to randomize the delay.
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// The delay before the `retry`-th retry, zero-based.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let delay = self
+            .base_delay
+            .saturating_mul(2_u32.saturating_pow(retry))
+            .min(self.max_delay);
+        if self.jitter && !delay.is_zero() {
+            rand::thread_rng().gen_range(delay / 2..=delay)
+        } else {
+            delay
+        }
+    }
+
+    /// Whether the request is worth retrying if the server responds `status`.
+    pub fn is_retryable_status(status: StatusCode) -> bool {
+        matches!(
+            status,
+            StatusCode::REQUEST_TIMEOUT
+                | StatusCode::TOO_MANY_REQUESTS
+                | StatusCode::INTERNAL_SERVER_ERROR
+                | StatusCode::BAD_GATEWAY
+                | StatusCode::SERVICE_UNAVAILABLE
+                | StatusCode::GATEWAY_TIMEOUT
+        )
+    }
+
+    /// Whether the request is worth retrying if it fails with `err`,
+    /// e.g. timeout or connection reset.
+    pub fn is_retryable_error(err: &reqwest::Error) -> bool {
+        err.is_timeout() || err.is_connect() || err.is_request() || err.is_body()
+    }
+
+    /// The delay required by the `Retry-After` header of `429` or `503` response.
+    fn retry_after(response: &Response) -> Option<Duration> {
+        if !matches!(
+            response.status(),
+            StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE
+        ) {
+            return None;
+        }
+        let retry_after = response.headers().get(RETRY_AFTER)?.to_str().ok()?;
+        match retry_after.parse() {
+            Ok(secs) => Some(Duration::from_secs(secs)),
+            Err(_) => httpdate::parse_http_date(retry_after)
+                .ok()?
+                .duration_since(SystemTime::now())
+                .ok(),
+        }
+    }
+
+    /// Send the `request` and parse the JSON response, retry on transient errors.
+    ///
+    /// If `request_rate` is set, each attempt acquires a token from it before sending.
+    ///
+    /// # Errors
+    ///
+    /// If the last attempt fails, or the error is not retryable,
+    /// e.g. `404 Not Found` or invalid JSON, this function will return the error.
+    ///
+    /// # Panics
+    ///
+    /// If the body of `request` is a stream, which can't be cloned for retrying.
+    pub async fn send_json<T: DeserializeOwned>(
+        &self,
+        request: RequestBuilder,
+        request_rate: Option<&RateLimiter>,
+    ) -> reqwest::Result<T> {
+        let mut retry = 0;
+        loop {
+            if let Some(request_rate) = request_rate {
+                request_rate.acquire(1).await;
+            }
+            let last = retry + 1 >= self.max_attempts.get();
+            let attempt = request
+                .try_clone()
+                .expect("streaming body can't be retried");
+            let delay = match attempt.send().await {
+                Ok(response) if !last && Self::is_retryable_status(response.status()) => {
+                    Self::retry_after(&response)
+                        .map_or_else(|| self.backoff(retry), |delay| delay.min(self.max_delay))
+                }
+                Ok(response) => match response.error_for_status()?.json().await {
+                    Err(err) if !last && Self::is_retryable_error(&err) => self.backoff(retry),
+                    result => return result,
+                },
+                Err(err) if !last && Self::is_retryable_error(&err) => self.backoff(retry),
+                Err(err) => return Err(err),
+            };
+            tokio::time::sleep(delay).await;
+            retry += 1;
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use reqwest::Client;
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    /// A server which responds `status` to the first `failures` requests, and `[]` to the rest.
+    async fn flaky_server(status: u16, failures: u64) -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(status).insert_header("Retry-After", "0"))
+            .up_to_n_times(failures)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw("[]", "application/json"))
+            .mount(&server)
+            .await;
+        server
+    }
+
+    fn policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy::new(
+            NonZeroU32::new(max_attempts).unwrap(),
+            Duration::from_millis(1),
+        )
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = policy(3).max_delay(Duration::from_millis(5));
+        for retry in 0..10 {
+            let delay = policy.backoff(retry);
+            let expected = Duration::from_millis(1 << retry.min(3)).min(policy.max_delay);
+            assert!(delay <= expected && delay >= expected / 2);
+        }
+        assert_eq!(
+            policy.jitter(false).backoff(u32::MAX),
+            Duration::from_millis(5)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_json_retry() -> reqwest::Result<()> {
+        let client = Client::new();
+
+        let server = flaky_server(503, 2).await;
+        let posts: Vec<()> = policy(3).send_json(client.get(server.uri()), None).await?;
+        assert!(posts.is_empty());
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+
+        let server = flaky_server(500, 2).await;
+        let err = policy(2)
+            .send_json::<Vec<()>>(client.get(server.uri()), None)
+            .await
+            .expect_err("the attempts should be exhausted");
+        assert_eq!(err.status(), Some(StatusCode::INTERNAL_SERVER_ERROR));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_send_json_not_retryable() {
+        let client = Client::new();
+        let server = flaky_server(404, 1).await;
+        let err = policy(3)
+            .send_json::<Vec<()>>(client.get(server.uri()), None)
+            .await
+            .expect_err("404 should not be retried");
+        assert_eq!(err.status(), Some(StatusCode::NOT_FOUND));
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+    }
+}
@@ -5,32 +5,39 @@
 //! Following is the low-level module wrapped by this module:
 //! - [`crate::download`]
 //! - [`crate::hash`]
+//! - [`crate::progress`]
+//! - [`crate::rate_limit`]
+//! - [`crate::retry`]
 //! - [`crate::tool`]
 
 use std::future::Future;
 use std::io::ErrorKind;
+use std::num::{NonZeroU64, NonZeroUsize};
 use std::path::{Path, PathBuf};
+use std::pin::pin;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::Context;
-use indicatif::{ProgressBar, ProgressFinish, ProgressStyle, WeakProgressBar};
+use futures::{future, stream, Stream, StreamExt};
 use reqwest::Client;
-use tokio::sync::Semaphore;
+use tokio::sync::{watch, Semaphore};
 use tokio::task::JoinSet;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
 
 use crate::api::data::field::Post;
-use crate::download::{DownloadError, Downloader};
+use crate::download::{write_atomic, DownloadError, Downloader};
 use crate::hash::hash_file;
+use crate::progress::{NoopObserver, ProgressObserver};
+use crate::rate_limit::RateLimiter;
+use crate::retry::RetryPolicy;
 use crate::tool::NUM_CPUS;
 
 type ApiPostData = Vec<Post>;
-
-const PB_FINISH_MODE: ProgressFinish = ProgressFinish::Abandon;
-const PB_TICK_SECS: u64 = 1;
-/// The time interval for updating the download speed.
-const SPEED_UPDATE_SECS: u64 = 1;
+/// The digest algorithm of [`Post::md5`].
+type Hasher = md5::Md5;
 
 /// The result of a single download task.
 enum SingleDownloadResult {
@@ -38,43 +45,222 @@ enum SingleDownloadResult {
     Done,
     /// The file already existed.
     Existed,
+    /// The task was cancelled, see [`Scheduler::cancel_token`].
+    Cancelled,
+}
+
+/// The output of a single download task.
+struct SingleDownloadOutput {
+    /// The number of retries, no matter the download succeeded or not.
+    retries: u32,
+    /// See [`PostReport::elapsed`].
+    elapsed: Duration,
+    result: anyhow::Result<SingleDownloadResult>,
+}
+
+/// The outcome of downloading a post, see [`PostReport`].
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum DownloadOutcome {
+    /// The image was downloaded and the tags were written successfully.
+    Done,
+    /// The image already existed, so it was skipped.
+    Existed,
+    /// Failed to check, download or write the tags.
+    Failed(anyhow::Error),
+    /// The task was cancelled before the download started or completed,
+    /// see [`Scheduler::cancel_token`].
+    Cancelled,
+}
+
+/// The report of downloading a post, see [`DownloadReport`].
+#[non_exhaustive]
+#[derive(Debug)]
+pub struct PostReport {
+    /// The [`Post::id`].
+    pub id: u64,
+    /// The path of the image, the tag file is next to it with `txt` extension.
+    pub path: PathBuf,
+    /// The outcome of the download.
+    pub outcome: DownloadOutcome,
+    /// The bytes transferred, including the failed attempts.
+    pub bytes: u64,
+    /// The number of retries, see [`Scheduler::retry`].
+    pub retries: u32,
+    /// The time spent on downloading, including the retries.
+    /// Zero if the download was not started, e.g. the image already existed.
+    pub elapsed: Duration,
 }
 
-/// current download number status
-struct DownloadStatus {
-    /// the number of files that have been downloaded successfully
-    done: u64,
-    // the number of files that already,which means no need to download
-    existed: u64,
-    // the number of files that failed to download
-    failed: u64,
+/// The report returned by [`Scheduler::launch`] and [`Scheduler::launch_stream`].
+#[non_exhaustive]
+#[derive(Debug)]
+pub struct DownloadReport {
+    /// The reports of the posts, in the order of completion.
+    pub posts: Vec<PostReport>,
+    /// The total elapsed time.
+    pub elapsed: Duration,
+    /// The error yielded by the API data stream, see [`Scheduler::launch_stream`].
+    pub api_error: Option<reqwest::Error>,
+    /// Whether the [`Scheduler::cancel_token`] was cancelled,
+    /// if so, the report is partial.
+    pub cancelled: bool,
+}
+
+impl DownloadReport {
+    /// The number of posts that were downloaded successfully.
+    pub fn done(&self) -> usize {
+        self.count(|outcome| matches!(outcome, DownloadOutcome::Done))
+    }
+
+    /// The number of posts that already existed.
+    pub fn existed(&self) -> usize {
+        self.count(|outcome| matches!(outcome, DownloadOutcome::Existed))
+    }
+
+    /// The number of posts that failed.
+    pub fn failed(&self) -> usize {
+        self.count(|outcome| matches!(outcome, DownloadOutcome::Failed(_)))
+    }
+
+    /// The number of posts that were cancelled.
+    pub fn cancelled(&self) -> usize {
+        self.count(|outcome| matches!(outcome, DownloadOutcome::Cancelled))
+    }
+
+    /// The total bytes transferred.
+    pub fn bytes(&self) -> u64 {
+        self.posts.iter().map(|post| post.bytes).sum()
+    }
+
+    fn count(&self, predicate: impl Fn(&DownloadOutcome) -> bool) -> usize {
+        self.posts
+            .iter()
+            .filter(|post| predicate(&post.outcome))
+            .count()
+    }
+}
+
+/// The options shared by all download tasks.
+#[derive(Clone)]
+struct TaskOptions {
+    /// See [`Scheduler::retry`].
+    retry: RetryPolicy,
+    /// See [`Scheduler::fsync`].
+    fsync: bool,
+    /// See [`Scheduler::max_bytes_per_sec`].
+    bandwidth: Option<Arc<RateLimiter>>,
+    /// See [`Scheduler::max_requests_per_sec`].
+    request_rate: Option<Arc<RateLimiter>>,
+    /// See [`Scheduler::observer`].
+    observer: Arc<dyn ProgressObserver>,
+    /// See [`Scheduler::cancel_token`].
+    cancel_token: CancellationToken,
+    /// See [`Scheduler::grace_period`].
+    grace_period: Duration,
+    /// Cancelled after the grace period of `cancel_token`, to abort the in-flight downloads.
+    abort_token: CancellationToken,
+    /// See [`SchedulerHandle::pause`].
+    pause: Arc<watch::Sender<bool>>,
+}
+
+/// The semaphores shared by all download tasks.
+#[derive(Clone)]
+struct Semaphores {
+    /// See [`Scheduler::download_concurrency`].
+    download: Arc<Semaphore>,
+    /// See [`Scheduler::hash_concurrency`].
+    hash: Arc<Semaphore>,
+}
+
+/// The status of a [`Scheduler`], see [`SchedulerHandle::status`].
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchedulerStatus {
+    /// The downloads are running.
+    Running,
+    /// The downloads are paused, see [`SchedulerHandle::pause`].
+    Paused,
+}
+
+/// The handle to control a [`Scheduler`] while it's running, see [`Scheduler::handle`].
+#[derive(Debug, Clone)]
+pub struct SchedulerHandle {
+    pause: Arc<watch::Sender<bool>>,
+}
+
+impl SchedulerHandle {
+    /// Pause the downloads, it's a no-op if already paused.
+    ///
+    /// The in-flight downloads stop pulling chunks, and the other downloads don't send requests,
+    /// see [`crate::download::DownloadFutureBuilder::pause`].
+    /// The existence checks are not paused, because they don't use the network.
+    pub fn pause(&self) {
+        self.pause
+            .send_if_modified(|paused| !std::mem::replace(paused, true));
+    }
+
+    /// Resume the downloads, it's a no-op if not paused.
+    pub fn resume(&self) {
+        self.pause
+            .send_if_modified(|paused| std::mem::replace(paused, false));
+    }
+
+    /// Whether the downloads are paused.
+    pub fn status(&self) -> SchedulerStatus {
+        if *self.pause.borrow() {
+            SchedulerStatus::Paused
+        } else {
+            SchedulerStatus::Running
+        }
+    }
 }
 
 /** The scheduler to download images from the API data.
 
 - This struct will wrap a [`Downloader`] to download images from the `api_post_data` API data to the `download_dir`.
-    Also, it will write the [`tags`] to a tag file with the same name as the image file.
+  Also, it will write the [`tags`] to a tag file with the same name as the image file.
+
+  *If the file already exists, the download and tag writing will be skipped.*
+
+  Both files are written to a temporary sibling and renamed into place,
+  so a file with the final name is always complete.
+  The downloaded image is verified with [`md5`] while streaming.
+
+- The number of concurrent downloads will be limited by [`Scheduler::download_concurrency`],
+  and the number of concurrent existence checks (hashing files) will be limited by
+  [`Scheduler::hash_concurrency`].
+
+- The total download bandwidth can be limited by [`Scheduler::max_bytes_per_sec`],
+  and the request rate to the image hosts can be limited by [`Scheduler::max_requests_per_sec`].
+
+- The failed downloads will be retried with [`Scheduler::retry`] policy, if the error is
+  [retryable](DownloadError::is_retryable).
 
-    *If the file already exists, the download and tag writing will be skipped.*
+- The download progress is reported to [`Scheduler::observer`],
+  e.g. [`crate::progress::ProgressBarObserver`] displays a process bar in the terminal.
 
-- The number of concurrent downloads will be limited to the number of CPUs available.
+- Use [`Scheduler::launch_stream`] to start downloading as soon as the first page of API data arrives.
 
-- A process bar will be displayed to show the download status and speed when downloading images.
+- Use [`Scheduler::cancel_token`] to stop downloading gracefully, e.g. on Ctrl-C,
+  and [`Scheduler::handle`] to pause and resume the downloads.
 
 [`tags`]: crate::api::data::field::Post::tags
+[`md5`]: crate::api::data::field::Post::md5
 
 # Example
 ```no_run
 use reqwest::Client;
 use std::path::PathBuf;
-use booru_dl::api::BatchGetter;
+use booru_dl::api::{BatchGetter, Gelbooru};
 use booru_dl::scheduler::Scheduler;
 
 #[tokio::main]
 async fn main() {
     let client = Client::new();
+    let booru = Gelbooru::default();
 
-    let getter = BatchGetter::build(&client, "cat", 10).unwrap();
+    let getter = BatchGetter::build(&client, &booru, "cat", 10).unwrap();
     let api_post_data = getter.run().await.expect("Failed to get data from API");
 
     let scheduler = Scheduler::build(client, "download_dir", api_post_data).await.unwrap();
@@ -88,9 +274,21 @@ pub struct Scheduler {
     // get it from `downloader` field
     download_dir: PathBuf,
     api_post_data: ApiPostData,
+    options: TaskOptions,
+    download_concurrency: NonZeroUsize,
+    hash_concurrency: NonZeroUsize,
 }
 
 impl Scheduler {
+    /// The default number of concurrent downloads.
+    pub const DEFAULT_DOWNLOAD_CONCURRENCY: NonZeroUsize = match NonZeroUsize::new(8) {
+        Some(concurrency) => concurrency,
+        None => unreachable!(),
+    };
+
+    /// The default grace period for the in-flight downloads after cancellation.
+    pub const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(10);
+
     /// Create a new scheduler.
     ///
     /// Usually, you prefer to use [`crate::api`] to get the `api_post_data`.
@@ -111,9 +309,136 @@ impl Scheduler {
             downloader,
             download_dir,
             api_post_data: api_post_data.into(),
+            options: TaskOptions {
+                retry: RetryPolicy::DEFAULT,
+                fsync: false,
+                bandwidth: None,
+                request_rate: None,
+                observer: Arc::new(NoopObserver),
+                cancel_token: CancellationToken::new(),
+                grace_period: Self::DEFAULT_GRACE_PERIOD,
+                abort_token: CancellationToken::new(),
+                pause: Arc::new(watch::channel(false).0),
+            },
+            download_concurrency: Self::DEFAULT_DOWNLOAD_CONCURRENCY,
+            hash_concurrency: *NUM_CPUS,
         })
     }
 
+    /// Limit the total download bandwidth to `max_bytes_per_sec`, default to no limit.
+    ///
+    /// All downloads share a token bucket, see [`RateLimiter`].
+    /// The speed prefix of the process bar reflects the throttled rate.
+    pub fn max_bytes_per_sec(mut self, max_bytes_per_sec: NonZeroU64) -> Self {
+        self.options.bandwidth = Some(Arc::new(RateLimiter::new(max_bytes_per_sec)));
+        self
+    }
+
+    /// Limit the total request rate to the image hosts to `max_requests_per_sec`, default to no limit.
+    ///
+    /// This is independent of the API request rate,
+    /// see [`crate::api::AnyBooru::with_request_rate`].
+    pub fn max_requests_per_sec(mut self, max_requests_per_sec: NonZeroU64) -> Self {
+        self.options.request_rate = Some(Arc::new(RateLimiter::new(max_requests_per_sec)));
+        self
+    }
+
+    /// Set the maximum number of concurrent downloads,
+    /// default to [`Self::DEFAULT_DOWNLOAD_CONCURRENCY`].
+    ///
+    /// Downloading is network-bound, so it can be larger than the number of CPUs,
+    /// but be polite to the server.
+    pub fn download_concurrency(mut self, download_concurrency: NonZeroUsize) -> Self {
+        self.download_concurrency = download_concurrency;
+        self
+    }
+
+    /// Set the maximum number of concurrent existence checks,
+    /// default to the number of CPUs, see [`NUM_CPUS`].
+    ///
+    /// Each check hashes the existing file, which is CPU-bound and consumes max to 2MB memory.
+    pub fn hash_concurrency(mut self, hash_concurrency: NonZeroUsize) -> Self {
+        self.hash_concurrency = hash_concurrency;
+        self
+    }
+
+    /// Retry the failed downloads with `retry` policy, default to [`RetryPolicy::DEFAULT`].
+    ///
+    /// Only the [retryable](DownloadError::is_retryable) errors will be retried,
+    /// e.g. `404 Not Found` will not.
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.options.retry = retry;
+        self
+    }
+
+    /// Sync the images and tag files to disk before renaming them into place, default to `false`.
+    ///
+    /// See [`crate::download::DownloadFutureBuilder::fsync`].
+    pub fn fsync(mut self, fsync: bool) -> Self {
+        self.options.fsync = fsync;
+        self
+    }
+
+    /// Report the download progress to `observer`, default to [`NoopObserver`].
+    ///
+    /// Keep a clone of `observer` if you want to query it after launching.
+    pub fn observer(mut self, observer: Arc<dyn ProgressObserver>) -> Self {
+        self.options.observer = observer;
+        self
+    }
+
+    /// Stop the scheduler gracefully when `cancel_token` is cancelled, default to never.
+    ///
+    /// Once cancelled, no more api data will be polled, and the tasks which haven't started
+    /// downloading are reported as [`DownloadOutcome::Cancelled`].
+    /// The in-flight downloads are allowed to complete within [`Self::grace_period`],
+    /// after that they are aborted and reported as [`DownloadOutcome::Cancelled`] too.
+    ///
+    /// An aborted download leaves no file with the final name, but a part file,
+    /// which will be resumed next time, see [`crate::download::DownloadFutureBuilder::build`].
+    pub fn cancel_token(mut self, cancel_token: CancellationToken) -> Self {
+        self.options.cancel_token = cancel_token;
+        self
+    }
+
+    /// Set the grace period for the in-flight downloads after cancellation,
+    /// default to [`Self::DEFAULT_GRACE_PERIOD`], see [`Self::cancel_token`].
+    pub fn grace_period(mut self, grace_period: Duration) -> Self {
+        self.options.grace_period = grace_period;
+        self
+    }
+
+    /// Create a handle to pause and resume the downloads, even after launching.
+    ///
+    /// The handle controls the run of this scheduler. Since [`Self::launch`] and [`Self::launch_stream`]
+    /// consume the scheduler, create the handle before launching, then use it while the launch future runs.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use reqwest::Client;
+    /// use booru_dl::scheduler::Scheduler;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let scheduler = Scheduler::build(Client::new(), "download_dir", Vec::new())
+    ///         .await
+    ///         .unwrap();
+    ///     let handle = scheduler.handle();
+    ///     let launch = tokio::spawn(scheduler.launch());
+    ///
+    ///     handle.pause();
+    ///     tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+    ///     handle.resume();
+    ///     launch.await.unwrap();
+    /// }
+    /// ```
+    pub fn handle(&self) -> SchedulerHandle {
+        SchedulerHandle {
+            pause: self.options.pause.clone(),
+        }
+    }
+
     /// Check if the file already exists by comparing the MD5 hash.
     /// If the file does not exist, return `false`.
     ///
@@ -123,8 +448,6 @@ impl Scheduler {
         filepath: impl AsRef<Path>,
         hashed_value: impl AsRef<str>,
     ) -> std::io::Result<bool> {
-        type Hasher = md5::Md5;
-
         hash_file::<Hasher>(filepath)
             .await
             .map(|file_md5| file_md5 == hashed_value.as_ref())
@@ -137,245 +460,413 @@ impl Scheduler {
             })
     }
 
-    /// Return the formatted download status message
-    #[inline]
-    fn pb_msg(status: &DownloadStatus) -> String {
-        let DownloadStatus {
-            done,
-            existed,
-            failed,
-        } = status;
-        format!("[done:{done}\texisted:{existed}\tfailed:{failed}]")
-    }
-
-    /// Return the formatted speed status message in bytes
-    #[inline]
-    fn pb_prefix(speed: u64) -> String {
-        format!("[{}/S]", indicatif::HumanBytes(speed))
-    }
-
-    /// Build a process bar with a specific length and custom style.
-    #[inline]
-    fn build_process_bar(len: u64) -> ProgressBar {
-        // see: https://docs.rs/indicatif/latest/indicatif/#templates
-        const PROCESS_CHARS: &str = "#>-";
-        // `prefix` for speed, `msg` for download status
-        const TEMPLATE: &str = "[{elapsed_precise}] {prefix} [{wide_bar:.cyan/blue}] {msg} {human_pos}/{human_len} ({eta})";
-
-        let style = ProgressStyle::with_template(TEMPLATE)
-            .unwrap()
-            .progress_chars(PROCESS_CHARS);
-
-        ProgressBar::new(len)
-            .with_style(style)
-            .with_message(Self::pb_msg(&DownloadStatus {
-                done: 0,
-                existed: 0,
-                failed: 0,
-            }))
-            .with_prefix(Self::pb_prefix(0))
-            .with_finish(PB_FINISH_MODE)
-    }
-
     /// Download a single file.
     ///
-    /// - `semaphore`: limit the number of concurrent downloads.
+    /// - `semaphores`: limit the number of concurrent downloads and existence checks.
+    /// - `id`: the [`Post::id`] to report to [`ProgressObserver::on_task_start`].
     /// - `filepath`: the path to save the file.
     /// - `md5`: the MD5 hash to compare for checking if the file already exists.
-    /// - `tags`: the tags to write to the tag file.
-    /// - `download_future`: the future to download the file,
-    ///     created by [`crate::download::DownloadFutureBuilder::build`].
+    /// - `tags`: the tags to write to the tag file, which is also written
+    ///   if the file already existed but the tag file is missing.
+    /// - `options`: the policy to retry the [retryable](DownloadError::is_retryable) download errors,
+    ///   whether to sync the tag file to disk (see [`write_atomic`]), the observer,
+    ///   and the tokens to cancel the task before downloading, or abort the download.
+    ///   The errors of the attempts during which the downloads were paused,
+    ///   e.g. the connection timed out while paused, don't count towards [`RetryPolicy::max_attempts`].
+    /// - `download_future`: create the future to download the file for each attempt,
+    ///   see [`crate::download::DownloadFutureBuilder::build`].
     #[inline]
-    async fn single_download(
-        semaphore: Arc<Semaphore>,
+    async fn single_download<F>(
+        semaphores: Semaphores,
+        id: u64,
         filepath: PathBuf,
         md5: String,
         tags: String,
-        download_future: impl Future<Output = Result<PathBuf, DownloadError>>,
-    ) -> anyhow::Result<SingleDownloadResult> {
-        // we must use semaphore to limit the number of concurrent downloads,
-        // because `check_file_existed` will hold a file handle, and consume 2MB memory
-        let _permit = semaphore
-            .acquire()
-            .await
-            .expect("semaphore was closed too early");
-
-        // check if the file existed
-        if Self::check_file_existed(&filepath, md5)
-            .await
-            .with_context(|| {
-                format!(
-                    "Failed to check if file is already existed: {}",
-                    filepath.display()
-                )
-            })?
-        {
-            return Ok(SingleDownloadResult::Existed);
-        }
-
-        // download the file
-        download_future
-            .await
-            .with_context(|| format!("Failed to download: {}", filepath.display()))?;
-
-        // write tags to file
+        options: TaskOptions,
+        download_future: impl Fn() -> F,
+    ) -> SingleDownloadOutput
+    where
+        F: Future<Output = Result<PathBuf, DownloadError>>,
+    {
+        let TaskOptions {
+            retry,
+            fsync,
+            observer,
+            cancel_token,
+            abort_token,
+            pause,
+            ..
+        } = options;
+        let mut pause = pause.subscribe();
+        let mut retries = 0;
+        let mut download_start = None;
         let tag_file_path = filepath.with_extension("txt");
-        tokio::fs::write(&tag_file_path, tags.replace(' ', ", ")) // "a b" -> "a, b"
-            .await
-            .with_context(|| format!("Failed to write tags: {}", tag_file_path.display()))?;
+        let write_tags = || async {
+            write_atomic(&tag_file_path, tags.replace(' ', ", "), fsync) // "a b" -> "a, b"
+                .await
+                .with_context(|| format!("Failed to write tags: {}", tag_file_path.display()))
+        };
+        let result = async {
+            // we must use semaphore to limit the number of concurrent checks,
+            // because `check_file_existed` will hold a file handle, and consume 2MB memory
+            let hash_permit = tokio::select! {
+                biased;
+                () = cancel_token.cancelled() => return Ok(SingleDownloadResult::Cancelled),
+                permit = semaphores.hash.acquire() => permit.expect("semaphore was closed too early"),
+            };
 
-        // success = download + write tags
-        Ok(SingleDownloadResult::Done)
-    }
+            // check if the file existed
+            if Self::check_file_existed(&filepath, md5)
+                .await
+                .with_context(|| {
+                    format!(
+                        "Failed to check if file is already existed: {}",
+                        filepath.display()
+                    )
+                })?
+            {
+                // the previous run may be interrupted after the file was renamed into place,
+                // but before the tags were written
+                if !tokio::fs::try_exists(&tag_file_path)
+                    .await
+                    .with_context(|| {
+                        format!(
+                            "Failed to check if tag file is already existed: {}",
+                            tag_file_path.display()
+                        )
+                    })?
+                {
+                    write_tags().await?;
+                }
+                return Ok(SingleDownloadResult::Existed);
+            }
+            drop(hash_permit);
 
-    /// Update the download speed prefix of `process_bar` every `SPEED_UPDATE_SECS` seconds forever,
-    /// until `process_bar` bar was dropped.
-    ///
-    /// The `speed_cursor` will be swapped(Ordering::Acquire) to 0 after each update.
-    #[inline]
-    async fn update_speed(process_bar: WeakProgressBar, speed_cursor: Arc<AtomicUsize>) {
-        const ORDER: Ordering = Ordering::Acquire;
+            let _download_permit = tokio::select! {
+                biased;
+                () = cancel_token.cancelled() => return Ok(SingleDownloadResult::Cancelled),
+                permit = semaphores.download.acquire() => permit.expect("semaphore was closed too early"),
+            };
+            download_start = Some(Instant::now());
+            observer.on_task_start(id);
 
-        let mut interval = tokio::time::interval(Duration::from_secs(SPEED_UPDATE_SECS));
-        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            // download the file, and retry on the transient errors
+            let download = async {
+                loop {
+                    pause.mark_unchanged();
+                    let result = download_future().await;
+                    // the pause state was changed during this attempt, or is still paused
+                    let paused = pause.has_changed().unwrap_or(false) || *pause.borrow();
+                    match result {
+                        Ok(_) => return Ok(()),
+                        // the next attempt waits until resumed, see `DownloadFutureBuilder::pause`
+                        Err(err) if err.is_retryable() && paused => {}
+                        Err(err)
+                            if err.is_retryable() && retries + 1 < retry.max_attempts.get() =>
+                        {
+                            tokio::time::sleep(retry.backoff(retries)).await;
+                            retries += 1;
+                        }
+                        Err(err) => {
+                            return Err(err).with_context(|| {
+                                format!(
+                                    "Failed to download after {} attempt(s): {}",
+                                    retries + 1,
+                                    filepath.display()
+                                )
+                            });
+                        }
+                    }
+                }
+            };
+            // the download future is dropped when aborted, which leaves the part file for resuming
+            tokio::select! {
+                result = download => result?,
+                () = abort_token.cancelled() => return Ok(SingleDownloadResult::Cancelled),
+            }
 
-        interval.tick().await; // The first tick completes immediately.
-        speed_cursor.swap(0, ORDER); // ignore previous data
+            // write tags to file
+            write_tags().await?;
 
-        if process_bar.upgrade().is_none() {
-            // process bar was dropped, so we exit
-            return;
+            // success = download + write tags
+            Ok(SingleDownloadResult::Done)
         }
+        .await;
+        SingleDownloadOutput {
+            retries,
+            elapsed: download_start.map_or(Duration::ZERO, |start| start.elapsed()),
+            result,
+        }
+    }
 
-        loop {
-            // Calculate the average speed over time
-            let last_instant = tokio::time::Instant::now();
-            interval.tick().await;
-            let elapsed: u64 = last_instant
-                .elapsed()
-                .as_millis()
-                .try_into()
-                .expect("elapsed time is too long to `u64`");
-            let current_size: u64 = speed_cursor
-                .swap(0, ORDER)
-                .try_into()
-                .expect("speed data is too large to `u64`");
-            // multiply by 1000 because `elapsed` is in milliseconds
-            let speed = (current_size * 1000) / elapsed;
-
-            if let Some(process_bar) = process_bar.upgrade() {
-                process_bar.set_prefix(Self::pb_prefix(speed));
-            } else {
-                // process bar was dropped, so we exit
-                return;
-            }
+    /// Spawn a download task into `download_join_set` for each post of `api_post_data`.
+    #[inline]
+    fn arrange(
+        downloader: &Downloader,
+        download_dir: &Path,
+        semaphores: &Semaphores,
+        options: &TaskOptions,
+        download_join_set: &mut JoinSet<PostReport>,
+        api_post_data: ApiPostData,
+    ) {
+        for data in api_post_data {
+            let Post {
+                id,
+                md5,
+                file_url,
+                filename,
+                tags,
+                ..
+            } = data;
+
+            options.observer.on_task_queued(id);
+            let bytes_cursor = Arc::new(AtomicUsize::new(0));
+            let on_data_observer = options.observer.clone();
+            let on_response_observer = options.observer.clone();
+            let download_future_builder = downloader
+                .future(file_url, &filename)
+                .add_data_cursor(Arc::downgrade(&bytes_cursor))
+                .on_data(move |bytes| on_data_observer.on_bytes(id, bytes))
+                .on_response(move |content_length| {
+                    on_response_observer.on_response(id, content_length)
+                })
+                .pause(options.pause.subscribe())
+                .fsync(options.fsync)
+                .verify::<Hasher>(md5.clone());
+            let download_future_builder = match &options.bandwidth {
+                Some(bandwidth) => download_future_builder.bandwidth(bandwidth.clone()),
+                None => download_future_builder,
+            };
+            let download_future_builder = match &options.request_rate {
+                Some(request_rate) => download_future_builder.request_rate(request_rate.clone()),
+                None => download_future_builder,
+            };
+            let path = download_dir.join(filename);
+            let single_download = Self::single_download(
+                semaphores.clone(),
+                id,
+                path.clone(),
+                md5,
+                tags,
+                options.clone(),
+                move || download_future_builder.clone().build(),
+            );
+            download_join_set.spawn(async move {
+                let SingleDownloadOutput {
+                    retries,
+                    elapsed,
+                    result,
+                } = single_download.await;
+                let outcome = match result {
+                    Ok(SingleDownloadResult::Done) => DownloadOutcome::Done,
+                    Ok(SingleDownloadResult::Existed) => DownloadOutcome::Existed,
+                    Ok(SingleDownloadResult::Cancelled) => DownloadOutcome::Cancelled,
+                    Err(err) => DownloadOutcome::Failed(err),
+                };
+                PostReport {
+                    id,
+                    path,
+                    outcome,
+                    bytes: bytes_cursor.load(Ordering::Acquire).try_into().unwrap(),
+                    retries,
+                    elapsed,
+                }
+            });
         }
     }
 
-    /// Update the download status message of `process_bar` until all tasks of `download_join_set` are completed.
+    /// Arrange download tasks through `arrange` for the api data from `api_post_stream`,
+    /// and report the completed tasks to the observer of `options` until all tasks are completed.
+    ///
+    /// If `api_post_stream` yields an error, no more tasks will be arranged,
+    /// and the error will be reported after all arranged tasks are completed.
+    ///
+    /// If the cancel token of `options` is cancelled, no more tasks will be arranged,
+    /// and the abort token will be cancelled after the grace period.
+    ///
+    /// The pause state of `options` is reported to the observer too.
     ///
     /// # Panics
     ///
     /// If a task panic, the panic will be resumed when `join` the task.
     #[inline]
     async fn update_status(
-        process_bar: ProgressBar,
-        mut download_join_set: JoinSet<anyhow::Result<SingleDownloadResult>>,
-    ) {
-        let mut status = DownloadStatus {
-            done: 0,
-            existed: 0,
-            failed: 0,
-        };
-        // Check result and update process bar
-        while let Some(task_result) = download_join_set.join_next().await {
-            let task_result = match task_result {
-                Ok(task_result) => task_result,
-                Err(join_error) => {
-                    if let Ok(reason) = join_error.try_into_panic() {
-                        // Expect unknown error, so we just resume the panic
-                        std::panic::resume_unwind(reason)
-                    }
-                    // task was cancelled if not panic, so we do nothing here
-                    panic!("Unexpected task cancelled");
-                }
-            };
+        options: &TaskOptions,
+        api_post_stream: impl Stream<Item = reqwest::Result<ApiPostData>>,
+        mut arrange: impl FnMut(ApiPostData, &mut JoinSet<PostReport>),
+    ) -> DownloadReport {
+        let TaskOptions {
+            observer,
+            cancel_token,
+            grace_period,
+            abort_token,
+            pause,
+            ..
+        } = options;
+        let start = Instant::now();
+        let mut api_post_stream = pin!(api_post_stream);
+        let mut stream_done = false;
+        let mut api_error = None;
+        let mut posts = Vec::new();
+        let mut cancelled = false;
+        // reset when cancelled
+        let mut grace_period_sleep = pin!(tokio::time::sleep(Duration::ZERO));
 
-            match task_result {
-                Ok(SingleDownloadResult::Done) => {
-                    status.done += 1;
-                }
-                Ok(SingleDownloadResult::Existed) => {
-                    status.existed += 1;
-                }
-                // why `suspend`: https://docs.rs/indicatif/0.17.8/indicatif/struct.ProgressBar.html#method.suspend
-                // why `{:#}`: https://docs.rs/anyhow/1.0.86/anyhow/struct.Error.html#display-representations
-                Err(err) => {
-                    status.failed += 1;
-                    process_bar.suspend(|| eprintln!("{:#}", err));
+        let mut pause = pause.subscribe();
+        // the scheduler may be paused before launching
+        if *pause.borrow_and_update() {
+            observer.on_pause();
+        }
+
+        let mut download_join_set = JoinSet::new();
+        loop {
+            // the following branches are always pending, so they must be disabled when all done,
+            // or `else` will never be reached
+            let running = !stream_done || !download_join_set.is_empty();
+            tokio::select! {
+                // Stop arranging tasks, and wait for the in-flight downloads within the grace period,
+                // unless there is nothing to cancel
+                () = cancel_token.cancelled(), if !cancelled && running => {
+                    cancelled = true;
+                    stream_done = true;
+                    grace_period_sleep.as_mut().reset(Instant::now() + *grace_period);
+                },
+                // Abort the in-flight downloads
+                () = &mut grace_period_sleep, if cancelled
+                    && !abort_token.is_cancelled()
+                    && !download_join_set.is_empty() => abort_token.cancel(),
+                // Report the pause state
+                Ok(()) = pause.changed(), if running => {
+                    if *pause.borrow_and_update() {
+                        observer.on_pause();
+                    } else {
+                        observer.on_resume();
+                    }
+                },
+                // Arrange tasks as soon as the api data arrives
+                api_post_data = api_post_stream.next(), if !stream_done => match api_post_data {
+                    Some(Ok(api_post_data)) => {
+                        arrange(api_post_data, &mut download_join_set);
+                    }
+                    Some(Err(err)) => {
+                        stream_done = true;
+                        api_error = Some(err);
+                    }
+                    None => stream_done = true,
+                },
+                // Check result and report to the observer
+                Some(task_result) = download_join_set.join_next() => {
+                    let post_report = match task_result {
+                        Ok(post_report) => post_report,
+                        Err(join_error) => {
+                            if let Ok(reason) = join_error.try_into_panic() {
+                                // Expect unknown error, so we just resume the panic
+                                std::panic::resume_unwind(reason)
+                            }
+                            // task was cancelled if not panic, so we do nothing here
+                            panic!("Unexpected task cancelled");
+                        }
+                    };
+                    observer.on_task_done(&post_report);
+                    posts.push(post_report);
                 }
+                // the stream is done and all tasks are completed
+                else => break,
             }
-            process_bar.set_message(Self::pb_msg(&status));
-            process_bar.inc(1);
         }
-        process_bar.finish();
+        let report = DownloadReport {
+            posts,
+            elapsed: start.elapsed(),
+            api_error,
+            cancelled,
+        };
+        observer.on_finish(&report);
+        report
     }
 
     /// Launch the scheduler and download all images from api data to the download directory.
-    /// A process bar will be displayed to show the download status and speed.
+    /// The download progress is reported to [`Self::observer`].
+    ///
+    /// Return the [`DownloadReport`] of all posts after all downloads are completed,
+    /// the failed downloads are reported as [`DownloadOutcome::Failed`] instead of an error.
+    /// If [`Self::cancel_token`] is cancelled, return the partial report after the in-flight
+    /// downloads are completed or aborted.
     ///
     /// # Panics
     ///
     /// If one of the download tasks panic, the panic will be resumed when `join` the task.
     ///
     /// Usually, this will **not happen**. If you encounter this situation, please report it as a bug.
-    pub async fn launch(self) {
+    pub async fn launch(self) -> DownloadReport {
+        self.launch_stream(stream::empty()).await
+    }
+
+    /// Same as [`Self::launch`], but also download the images from `api_post_stream`,
+    /// each api data is arranged as soon as it arrives, e.g. from [`crate::api::BatchGetter::stream`].
+    ///
+    /// If `api_post_stream` yields an error, no more api data will be polled,
+    /// and the error will be reported as [`DownloadReport::api_error`]
+    /// after the arranged downloads are completed.
+    ///
+    /// # Panics
+    ///
+    /// See [`Self::launch`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use reqwest::Client;
+    /// use booru_dl::api::{BatchGetter, Gelbooru};
+    /// use booru_dl::scheduler::Scheduler;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> reqwest::Result<()> {
+    ///     let client = Client::new();
+    ///     let booru = Gelbooru::default();
+    ///
+    ///     let getter = BatchGetter::build(&client, &booru, "cat", 10).unwrap();
+    ///
+    ///     let scheduler = Scheduler::build(client.clone(), "download_dir", Vec::new())
+    ///         .await
+    ///         .unwrap();
+    ///     let report = scheduler.launch_stream(getter.stream()).await;
+    ///     if let Some(err) = report.api_error {
+    ///         return Err(err);
+    ///     }
+    ///     println!("failed: {}", report.failed());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn launch_stream(
+        self,
+        api_post_stream: impl Stream<Item = reqwest::Result<ApiPostData>>,
+    ) -> DownloadReport {
         let Self {
             downloader,
             download_dir,
             api_post_data,
+            options,
+            download_concurrency,
+            hash_concurrency,
         } = self;
+        let api_post_stream = stream::once(future::ready(Ok(api_post_data))).chain(api_post_stream);
 
-        let process_bar = Self::build_process_bar(api_post_data.len().try_into().unwrap());
-        process_bar.enable_steady_tick(Duration::from_secs(PB_TICK_SECS));
-
-        let speed_cursor = Arc::new(AtomicUsize::new(0));
-        let semaphore = Arc::new(Semaphore::new(NUM_CPUS.get()));
-        let mut download_join_set = JoinSet::new();
-        // Arrange tasks
-        process_bar.suspend(|| eprintln!("Arranging tasks..."));
-        for data in api_post_data {
-            let Post {
-                md5,
-                file_url,
-                filename,
-                tags,
-                ..
-            } = data;
-
-            let download_future = downloader
-                .future(file_url, &filename)
-                .add_data_cursor(Arc::downgrade(&speed_cursor))
-                .build();
-            download_join_set.spawn(Self::single_download(
-                semaphore.clone(),
-                download_dir.join(filename),
-                md5,
-                tags,
-                download_future,
-            ));
-        }
-
-        process_bar.suspend(|| eprintln!("Arranging tasks done"));
-
-        // NOTE: We update the download speed only after arranging all tasks,
-        // otherwise there may be a situation where the download progress remains unchanged while the speed keeps changing
-        let update_speed = Self::update_speed(process_bar.downgrade(), speed_cursor);
-        let update_status = Self::update_status(process_bar, download_join_set);
+        let semaphores = Semaphores {
+            download: Arc::new(Semaphore::new(download_concurrency.get())),
+            hash: Arc::new(Semaphore::new(hash_concurrency.get())),
+        };
+        let arrange = |api_post_data, download_join_set: &mut JoinSet<_>| {
+            Self::arrange(
+                &downloader,
+                &download_dir,
+                &semaphores,
+                &options,
+                download_join_set,
+                api_post_data,
+            )
+        };
 
-        // Note: `join!` `update_speed` may wait an additional `SPEED_UPDATE_SECS` seconds,
-        // use `select!` if you want to avoid this.
-        tokio::join!(update_speed, update_status);
+        Self::update_status(&options, api_post_stream, arrange).await
     }
 }
 
@@ -383,11 +874,15 @@ impl Scheduler {
 mod tests {
     use super::*;
 
+    use std::num::NonZeroU32;
     use std::sync::LazyLock;
 
+    use serde_json::json;
     use tempfile::TempDir;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
 
-    use crate::api::PostInner;
+    use crate::api::gelbooru;
 
     const MD5: &str = "9e107d9d372bb6826bd81d3542a419d6";
     const CONTENT: &str = "The quick brown fox jumps over the lazy dog";
@@ -397,15 +892,20 @@ mod tests {
     static CONTENT_FILE_NAME: LazyLock<String> = LazyLock::new(|| format!("{ID}.{EXT}"));
     static EMPTY_FILE_NAME: LazyLock<String> = LazyLock::new(|| format!("empty.{EXT}"));
 
+    fn post_data(id: u64, file_url: impl Into<String>) -> Post {
+        let post: gelbooru::data::field::Post = serde_json::from_value(json!({
+            "id": id,
+            "tags": "foo bar",
+            "md5": MD5,
+            "file_url": file_url.into(),
+            "image": format!("{MD5}.{EXT}"),
+        }))
+        .unwrap();
+        post.into()
+    }
+
     fn default_post_data() -> Post {
-        PostInner {
-            id: ID,
-            tags: String::from("foo bar"),
-            md5: String::from(MD5),
-            file_url: String::from(FILE_URL),
-            image: PathBuf::from(format!("{MD5}.{EXT}")),
-        }
-        .into()
+        post_data(ID, FILE_URL)
     }
 
     struct DefaultScheduler {
@@ -456,9 +956,432 @@ mod tests {
         assert!(!is_existed);
     }
 
+    #[tokio::test]
+    async fn test_single_download_retry() {
+        let server = MockServer::start().await;
+        // the first two requests fail, then the server recovers
+        Mock::given(method("GET"))
+            .and(path("/flaky.jpg"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(2)
+            .with_priority(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/flaky.jpg"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(CONTENT))
+            .mount(&server)
+            .await;
+
+        let temp_dir = TempDir::new().unwrap();
+        let downloader = Downloader::session(Client::new(), temp_dir.path());
+        let retry = RetryPolicy::new(NonZeroU32::new(3).unwrap(), Duration::from_millis(1));
+        let single_download = |filename: &str| {
+            let download_future_builder =
+                downloader.future(format!("{}/{filename}", server.uri()), filename);
+            Scheduler::single_download(
+                Semaphores {
+                    download: Arc::new(Semaphore::new(1)),
+                    hash: Arc::new(Semaphore::new(1)),
+                },
+                ID,
+                temp_dir.path().join(filename),
+                String::from("whatever md5"),
+                String::from("foo bar"),
+                TaskOptions {
+                    retry: retry.clone(),
+                    fsync: false,
+                    bandwidth: None,
+                    request_rate: None,
+                    observer: Arc::new(NoopObserver),
+                    cancel_token: CancellationToken::new(),
+                    grace_period: Scheduler::DEFAULT_GRACE_PERIOD,
+                    abort_token: CancellationToken::new(),
+                    pause: Arc::new(watch::channel(false).0),
+                },
+                move || download_future_builder.clone().build(),
+            )
+        };
+
+        let output = single_download("flaky.jpg").await;
+        assert_eq!(output.retries, 2);
+        assert!(matches!(output.result, Ok(SingleDownloadResult::Done)));
+        assert_eq!(
+            std::fs::read_to_string(temp_dir.path().join("flaky.jpg")).unwrap(),
+            CONTENT
+        );
+
+        // `404 Not Found` is not retryable
+        let output = single_download("missing.jpg").await;
+        assert_eq!(output.retries, 0);
+        assert!(output.result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_single_download_paused_timeout() {
+        const TIMEOUT: Duration = Duration::from_millis(200);
+
+        let server = MockServer::start().await;
+        // the first request times out while paused, then the server responds in time
+        Mock::given(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(CONTENT)
+                    .set_delay(TIMEOUT * 2),
+            )
+            .up_to_n_times(1)
+            .with_priority(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(CONTENT))
+            .mount(&server)
+            .await;
+
+        let temp_dir = TempDir::new().unwrap();
+        let client = Client::builder().timeout(TIMEOUT).build().unwrap();
+        let downloader = Downloader::session(client, temp_dir.path());
+        let pause = Arc::new(watch::channel(false).0);
+        let handle = SchedulerHandle {
+            pause: pause.clone(),
+        };
+        let download_future_builder = downloader
+            .future(server.uri(), CONTENT_FILE_NAME.as_str())
+            .pause(pause.subscribe());
+        let single_download = Scheduler::single_download(
+            Semaphores {
+                download: Arc::new(Semaphore::new(1)),
+                hash: Arc::new(Semaphore::new(1)),
+            },
+            ID,
+            temp_dir.path().join(&(*CONTENT_FILE_NAME)),
+            String::from("whatever md5"),
+            String::from("foo bar"),
+            TaskOptions {
+                // no retry at all
+                retry: RetryPolicy::new(NonZeroU32::MIN, Duration::ZERO),
+                fsync: false,
+                bandwidth: None,
+                request_rate: None,
+                observer: Arc::new(NoopObserver),
+                cancel_token: CancellationToken::new(),
+                grace_period: Scheduler::DEFAULT_GRACE_PERIOD,
+                abort_token: CancellationToken::new(),
+                pause,
+            },
+            move || download_future_builder.clone().build(),
+        );
+        let pause_and_resume = async {
+            tokio::time::sleep(TIMEOUT / 2).await;
+            handle.pause();
+            tokio::time::sleep(TIMEOUT).await;
+            handle.resume();
+        };
+
+        let ((), output) = tokio::join!(pause_and_resume, single_download);
+        // the timeout during the pause doesn't use up the attempts
+        assert!(matches!(output.result, Ok(SingleDownloadResult::Done)));
+        assert_eq!(output.retries, 0);
+        assert_eq!(server.received_requests().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_download_concurrency() {
+        const DELAY: Duration = Duration::from_millis(200);
+        const NUM_POSTS: u32 = 3;
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(CONTENT)
+                    .set_delay(DELAY),
+            )
+            .mount(&server)
+            .await;
+        let temp_dir = TempDir::new().unwrap();
+        let api_post_data: Vec<_> = (0..NUM_POSTS)
+            .map(|id| post_data(id.into(), format!("{}/{id}.{EXT}", server.uri())))
+            .collect();
+
+        let start = Instant::now();
+        let report = Scheduler::build(Client::new(), temp_dir.path(), api_post_data)
+            .await
+            .unwrap()
+            .download_concurrency(NonZeroUsize::MIN)
+            .launch()
+            .await;
+        // the downloads are not concurrent
+        assert!(start.elapsed() >= DELAY * NUM_POSTS);
+        assert_eq!(report.done(), NUM_POSTS as usize);
+        assert_eq!(report.bytes(), u64::from(NUM_POSTS) * CONTENT.len() as u64);
+        for id in 0..NUM_POSTS {
+            let content = std::fs::read_to_string(temp_dir.path().join(format!("{id}.{EXT}")));
+            assert_eq!(content.unwrap(), CONTENT);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cancel() {
+        const DELAY: Duration = Duration::from_millis(300);
+        const NUM_POSTS: u64 = 3;
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(CONTENT)
+                    .set_delay(DELAY),
+            )
+            .mount(&server)
+            .await;
+        // cancel while the first download is in flight
+        let server = &server;
+        let launch = |grace_period: Duration| async move {
+            let temp_dir = TempDir::new().unwrap();
+            let api_post_data: Vec<_> = (0..NUM_POSTS)
+                .map(|id| post_data(id, format!("{}/{id}.{EXT}", server.uri())))
+                .collect();
+            let cancel_token = CancellationToken::new();
+            let scheduler = Scheduler::build(Client::new(), temp_dir.path(), api_post_data)
+                .await
+                .unwrap()
+                .download_concurrency(NonZeroUsize::MIN)
+                .cancel_token(cancel_token.clone())
+                .grace_period(grace_period);
+            let cancel = async {
+                tokio::time::sleep(DELAY / 3).await;
+                cancel_token.cancel();
+            };
+            let ((), report) = tokio::join!(cancel, scheduler.launch());
+            report
+        };
+
+        // the in-flight download completes within the grace period
+        let report = launch(Duration::from_secs(10)).await;
+        assert!(report.cancelled);
+        assert_eq!(report.done(), 1);
+        assert_eq!(report.cancelled(), NUM_POSTS as usize - 1);
+
+        // the in-flight download is aborted after the grace period
+        let start = Instant::now();
+        let report = launch(Duration::ZERO).await;
+        assert!(start.elapsed() < DELAY);
+        assert!(report.cancelled);
+        assert_eq!(report.cancelled(), NUM_POSTS as usize);
+    }
+
+    #[tokio::test]
+    async fn test_pause() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(CONTENT))
+            .mount(&server)
+            .await;
+        let temp_dir = TempDir::new().unwrap();
+        let api_post_data = Vec::from([post_data(ID, format!("{}/{ID}.{EXT}", server.uri()))]);
+        let scheduler = Scheduler::build(Client::new(), temp_dir.path(), api_post_data)
+            .await
+            .unwrap();
+
+        let handle = scheduler.handle();
+        handle.pause();
+        assert_eq!(handle.status(), SchedulerStatus::Paused);
+        let launch = tokio::spawn(scheduler.launch());
+
+        // no request is sent while paused
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(!launch.is_finished());
+        assert!(server.received_requests().await.unwrap().is_empty());
+
+        handle.resume();
+        assert_eq!(handle.status(), SchedulerStatus::Running);
+        let report = launch.await.unwrap();
+        assert_eq!(report.done(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_pause_after_launch() {
+        const DELAY: Duration = Duration::from_millis(200);
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(CONTENT)
+                    .set_delay(DELAY),
+            )
+            .mount(&server)
+            .await;
+        let temp_dir = TempDir::new().unwrap();
+        let api_post_data: Vec<_> = (0..2)
+            .map(|id| post_data(id, format!("{}/{id}.{EXT}", server.uri())))
+            .collect();
+        let scheduler = Scheduler::build(Client::new(), temp_dir.path(), api_post_data)
+            .await
+            .unwrap()
+            .download_concurrency(NonZeroUsize::MIN);
+
+        // the handle must be created before launching, and controls the launched run
+        let handle = scheduler.handle();
+        let launch = tokio::spawn(scheduler.launch());
+        tokio::time::sleep(DELAY / 2).await;
+        handle.pause();
+
+        // the in-flight download completes, but the next one doesn't send the request while paused
+        tokio::time::sleep(DELAY * 3).await;
+        assert!(!launch.is_finished());
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+
+        handle.resume();
+        let report = launch.await.unwrap();
+        assert_eq!(report.done(), 2);
+    }
+
     #[tokio::test]
     async fn test_launch() {
         let default_scheduler = DefaultScheduler::new().await;
-        default_scheduler.inner.launch().await
+        let report = default_scheduler.inner.launch().await;
+        assert_eq!(report.posts.len(), 1);
+        // the content file is the same as the post
+        assert!(matches!(report.posts[0].outcome, DownloadOutcome::Existed));
+        assert_eq!(report.posts[0].id, ID);
+    }
+
+    #[tokio::test]
+    async fn test_launch_missing_tag_file() {
+        // e.g. the previous run was interrupted after renaming the file, but before writing the tags
+        let default_scheduler = DefaultScheduler::new().await;
+        let tag_file_path = default_scheduler
+            .temp_dir
+            .path()
+            .join(&(*CONTENT_FILE_NAME))
+            .with_extension("txt");
+        assert!(!tag_file_path.exists());
+
+        let report = default_scheduler.inner.launch().await;
+        assert!(matches!(report.posts[0].outcome, DownloadOutcome::Existed));
+        assert_eq!(std::fs::read_to_string(&tag_file_path).unwrap(), "foo, bar");
+    }
+
+    #[tokio::test]
+    async fn test_launch_stream() {
+        let default_scheduler = DefaultScheduler::new().await;
+        let api_post_stream = stream::iter([
+            Ok(Vec::from([default_post_data()])),
+            Ok(Vec::from([default_post_data()])),
+        ]);
+        let report = default_scheduler.inner.launch_stream(api_post_stream).await;
+        assert_eq!(report.existed(), 3);
+        assert!(report.api_error.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_observer() {
+        #[derive(Default)]
+        struct Recorder {
+            queued: AtomicUsize,
+            started: AtomicUsize,
+            content_length: AtomicUsize,
+            bytes: AtomicUsize,
+            done: AtomicUsize,
+            finished: AtomicUsize,
+        }
+
+        impl ProgressObserver for Recorder {
+            fn on_task_queued(&self, _: u64) {
+                self.queued.fetch_add(1, Ordering::Relaxed);
+            }
+            fn on_task_start(&self, _: u64) {
+                self.started.fetch_add(1, Ordering::Relaxed);
+            }
+            fn on_response(&self, _: u64, content_length: Option<u64>) {
+                self.content_length.fetch_add(
+                    content_length.unwrap().try_into().unwrap(),
+                    Ordering::Relaxed,
+                );
+            }
+            fn on_bytes(&self, id: u64, bytes: u64) {
+                assert_eq!(id, ID + 1);
+                self.bytes
+                    .fetch_add(bytes.try_into().unwrap(), Ordering::Relaxed);
+            }
+            fn on_task_done(&self, _: &PostReport) {
+                self.done.fetch_add(1, Ordering::Relaxed);
+            }
+            fn on_finish(&self, report: &DownloadReport) {
+                assert_eq!(report.posts.len(), self.done.load(Ordering::Relaxed));
+                self.finished.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(CONTENT))
+            .mount(&server)
+            .await;
+        let default_scheduler = DefaultScheduler::new().await;
+        let api_post_stream = stream::iter([Ok(Vec::from([post_data(
+            ID + 1,
+            format!("{}/{}.{EXT}", server.uri(), ID + 1),
+        )]))]);
+
+        let recorder = Arc::new(Recorder::default());
+        let report = default_scheduler
+            .inner
+            .observer(recorder.clone())
+            .launch_stream(api_post_stream)
+            .await;
+        assert_eq!(report.existed(), 1);
+        assert_eq!(report.done(), 1);
+        assert_eq!(recorder.queued.load(Ordering::Relaxed), 2);
+        // the existed one is not started
+        assert_eq!(recorder.started.load(Ordering::Relaxed), 1);
+        assert_eq!(
+            recorder.content_length.load(Ordering::Relaxed),
+            CONTENT.len()
+        );
+        assert_eq!(recorder.bytes.load(Ordering::Relaxed), CONTENT.len());
+        assert_eq!(recorder.done.load(Ordering::Relaxed), 2);
+        assert_eq!(recorder.finished.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_launch_stream_report() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(format!("/{ID}.{EXT}")))
+            .respond_with(ResponseTemplate::new(200).set_body_string(CONTENT))
+            .mount(&server)
+            .await;
+        let temp_dir = TempDir::new().unwrap();
+        let api_post_data = Vec::from([post_data(ID, format!("{}/{ID}.{EXT}", server.uri()))]);
+        let api_error = Client::new().get("not a url").build().unwrap_err();
+        let api_post_stream = stream::iter([
+            Ok(Vec::from([post_data(
+                ID + 1,
+                format!("{}/missing.{EXT}", server.uri()),
+            )])),
+            Err(api_error),
+            Ok(Vec::from([post_data(ID + 2, FILE_URL)])),
+        ]);
+
+        let report = Scheduler::build(Client::new(), temp_dir.path(), api_post_data)
+            .await
+            .unwrap()
+            .launch_stream(api_post_stream)
+            .await;
+        // no more posts are arranged after the error
+        assert_eq!(report.posts.len(), 2);
+        assert_eq!(report.done(), 1);
+        assert_eq!(report.failed(), 1);
+        assert!(report.api_error.is_some());
+        let failed = report
+            .posts
+            .iter()
+            .find(|post| matches!(post.outcome, DownloadOutcome::Failed(_)))
+            .unwrap();
+        assert_eq!(failed.id, ID + 1);
+        assert_eq!(failed.retries, 0);
     }
 }
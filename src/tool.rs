@@ -4,15 +4,50 @@
 //! [`crate::scheduler`] will automatically use these tools.
 
 use std::ffi::OsString;
+use std::fmt;
 use std::num::NonZeroUsize;
 use std::sync::LazyLock;
 use std::thread::available_parallelism;
 
+use serde::Deserialize;
+
 /// The number of CPUs available to the program.
 /// You can consider this as cache of [`std::thread::available_parallelism`].
 pub static NUM_CPUS: LazyLock<NonZeroUsize> =
     LazyLock::new(|| available_parallelism().unwrap_or(NonZeroUsize::new(1).unwrap()));
 
+/// A string that should never be displayed, e.g. an API key.
+///
+/// Both [`Debug`] and [`Display`](fmt::Display) print `***` instead of the content,
+/// use [`Self::expose`] to get the content.
+#[derive(Clone, PartialEq, Eq, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    /// Create a new secret.
+    pub fn new(secret: impl Into<String>) -> Self {
+        Self(secret.into())
+    }
+
+    /// Get the content of the secret. Be careful not to leak it.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("***")
+    }
+}
+
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("***")
+    }
+}
+
 /// Modify the file stem of the path.
 pub(crate) trait SetFileStem {
     fn set_file_stem(&mut self, stem: impl Into<OsString>);
@@ -45,4 +80,11 @@ mod tests {
         path.set_file_stem("test2");
         assert_eq!(path, std::path::PathBuf::from("test2.txt"));
     }
+
+    #[test]
+    fn test_secret_redacted() {
+        let secret = Secret::new("my_api_key");
+        assert_eq!(format!("{secret} {secret:?}"), "*** ***");
+        assert_eq!(secret.expose(), "my_api_key");
+    }
 }